@@ -1,9 +1,17 @@
+use crate::qc::activity;
+use crate::qc::options::OptionOverride;
+use crate::qc::placement::Placement;
+use crate::qc::sequence::MergeStatus;
 use crate::qc::QC;
-use anyhow::{bail, Result};
+use anyhow::{bail, Context, Result};
 use comfy_table::{Cell, Color, Row, Table};
 use console::{style, Term};
 use globber::Pattern;
+use regex::Regex;
+use std::collections::HashMap;
 use std::path::PathBuf;
+use std::str::FromStr;
+use structopt::clap::{Error, ErrorKind};
 use structopt::StructOpt;
 
 #[derive(StructOpt, Debug)]
@@ -20,36 +28,215 @@ pub struct Opt {
     pub output: PathBuf,
     /// qc file that has animations you want to use in the base file
     #[structopt(value_name("QC FILE"), short, long, parse(from_os_str))]
-    pub file: PathBuf,
+    pub file: Option<PathBuf>,
     /// base qc file that has animations that will be replaced/added
     #[structopt(value_name("QC FILE"), short, long, parse(from_os_str))]
-    pub base_file: PathBuf,
+    pub base_file: Option<PathBuf>,
     /// don't clear the console
     #[structopt(short, long)]
     pub dont_clear: bool,
+    /// merge into an existing output folder without deleting unrelated files
+    #[structopt(short, long)]
+    pub merge: bool,
+    /// edit the base qc file directly after backing it up, only the new smds are copied
+    /// next to it
+    #[structopt(short, long, conflicts_with("merge"))]
+    pub in_place: bool,
+    /// how many backups of the output folder or base qc are kept, the oldest are removed
+    /// after a merge
+    #[structopt(long, value_name("N"), default_value = "5")]
+    pub keep_backups: usize,
+    /// make smds with the same bytes share one file and rewrite their references
+    #[structopt(long)]
+    pub dedup: bool,
+    /// retarget the donor smds and weightlists to the base skeleton with a bone-mapping
+    /// table, one `"donor bone" "base bone"` pair per line
+    #[structopt(value_name("FILE"), long, parse(from_os_str))]
+    pub bone_map: Option<PathBuf>,
+    /// resample the smds of replaced sequences to the base sequence's fps and use that fps
+    #[structopt(long)]
+    pub conform_fps: bool,
+    /// keep the base's sound events on replaced sequences, their frames are rescaled to the
+    /// donor smd's length
+    #[structopt(long)]
+    pub keep_base_events: bool,
+    /// change options of merged sequences, e.g. `p_draw{fps=24}` or
+    /// `p_*{fadein=base,fadeout=base,node=base}`, `base` takes the base sequence's option
+    #[structopt(
+        value_name("SEQUENCES{OPTION=VALUE,...}"),
+        long = "override",
+        number_of_values(1)
+    )]
+    pub overrides: Vec<String>,
+    /// what to do when a donor $weightlist has the same name as a base one but different
+    /// weights: `ask`, `rename` the donor's, keep the `base`'s or use the `donor`'s for both
+    #[structopt(
+        long,
+        value_name("MODE"),
+        default_value = "ask",
+        possible_values(&["ask", "rename", "base", "donor"])
+    )]
+    pub weightlist_conflicts: Conflict,
+    /// what to do when a donor $animation has the same name as a base one but a different
+    /// block or smd: `ask`, `rename` the donor's, keep the `base`'s or use the `donor`'s
+    /// for both
+    #[structopt(
+        long,
+        value_name("MODE"),
+        default_value = "ask",
+        possible_values(&["ask", "rename", "base", "donor"])
+    )]
+    pub animation_conflicts: Conflict,
+    /// where appended blocks go: next to the blocks of the same `kind`, at the `end` of the
+    /// qc, in a `group` under a `// merged from <donor>` banner, after a base block with
+    /// `anchor:NAME`, or in a generated `qci` that the base qc $includes, the replaced
+    /// sequences, $animations and $weightlists are still changed in the base qc itself
+    #[structopt(long, value_name("MODE"), default_value = "kind")]
+    pub placement: Placement,
+    /// put a comment above every appended or replaced block with the donor qc, the block's
+    /// lines in it and the time of the merge, or RECIPE instead of the time so merging the
+    /// same files again gives the same qc
+    #[structopt(long, value_name("RECIPE"))]
+    pub provenance: Option<Option<String>>,
+    #[structopt(subcommand)]
+    pub cmd: Option<Command>,
+}
+
+#[derive(StructOpt, Debug)]
+pub enum Command {
+    /// lists the backups of the output folder and restores one
+    Restore {
+        /// the backup's number or folder name, lists the backups if not given
+        #[structopt(value_name("BACKUP"))]
+        backup: Option<String>,
+    },
+    /// resamples an smd to a different fps or number of frames and writes a new smd
+    Resample {
+        /// the smd to resample
+        #[structopt(value_name("SMD"), parse(from_os_str))]
+        smd: PathBuf,
+        /// where the resampled smd is written to
+        #[structopt(value_name("OUTPUT"), parse(from_os_str))]
+        output: PathBuf,
+        /// the fps the smd plays at
+        #[structopt(long, default_value = "30")]
+        from_fps: f64,
+        /// the fps the resampled smd will play at, it plays at the same speed
+        #[structopt(long, required_unless("frames"), conflicts_with("frames"))]
+        fps: Option<f64>,
+        /// the number of frames the resampled smd will have
+        #[structopt(long)]
+        frames: Option<usize>,
+    },
+    /// keeps a range of frames of an smd and writes a new smd
+    Trim {
+        /// the smd to trim
+        #[structopt(value_name("SMD"), parse(from_os_str))]
+        smd: PathBuf,
+        /// where the trimmed smd is written to
+        #[structopt(value_name("OUTPUT"), parse(from_os_str))]
+        output: PathBuf,
+        /// the first frame to keep, counting from 0
+        #[structopt(long, default_value = "0")]
+        start: usize,
+        /// the last frame to keep, the last frame of the smd if not given
+        #[structopt(long)]
+        end: Option<usize>,
+    },
+    /// reverses the frames of an smd and writes a new smd
+    Reverse {
+        /// the smd to reverse
+        #[structopt(value_name("SMD"), parse(from_os_str))]
+        smd: PathBuf,
+        /// where the reversed smd is written to
+        #[structopt(value_name("OUTPUT"), parse(from_os_str))]
+        output: PathBuf,
+    },
+    /// joins smds end-to-end and writes a new smd, e.g. a start, loop and end reload
+    Concat {
+        /// where the joined smd is written to
+        #[structopt(value_name("OUTPUT"), parse(from_os_str))]
+        output: PathBuf,
+        /// the smds to join in order, they must use the bones of the first one
+        #[structopt(value_name("SMD"), parse(from_os_str), required(true), min_values(2))]
+        smds: Vec<PathBuf>,
+        /// skip the first frame of every smd after the first, for when it's the same as the
+        /// last frame of the smd before it
+        #[structopt(long)]
+        drop_first: bool,
+    },
+}
+
+// how a donor $weightlist or $animation is merged when the base has a different one with
+// the same name
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Conflict {
+    // shows what's different and asks
+    Ask,
+    // appends the donor's with a new name, the donor's blocks use the new name
+    Rename,
+    // keeps the base's, the donor's blocks use it
+    Base,
+    // replaces the base's with the donor's, the base's blocks use it too
+    Donor,
+}
+
+impl FromStr for Conflict {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "ask" => Ok(Conflict::Ask),
+            "rename" => Ok(Conflict::Rename),
+            "base" => Ok(Conflict::Base),
+            "donor" => Ok(Conflict::Donor),
+            _ => bail!("Unknown conflict mode: `{}`", s),
+        }
+    }
+}
+
+impl Opt {
+    // the donor and base qc files, they're only optional when a subcommand is used
+    pub fn qc_files(&self) -> (PathBuf, PathBuf) {
+        match (&self.file, &self.base_file) {
+            (Some(file), Some(base_file)) => (file.to_owned(), base_file.to_owned()),
+            _ => Error::with_description(
+                "--file and --base-file are required unless a subcommand is used",
+                ErrorKind::MissingRequiredArgument,
+            )
+            .exit(),
+        }
+    }
 }
 
 pub struct SequenceTable(Table);
 
 impl SequenceTable {
-    // creates a new SequenceTable from a QC struct
-    pub fn from(qc: &QC) -> Self {
+    // creates a new SequenceTable from a QC struct, base_qc is used to show how each
+    // sequence will be merged
+    pub fn from(qc: &QC, base_qc: &QC) -> Self {
         let mut table = Table::new();
-        table.set_header(vec!["available animations", "  ", "selected animations"]);
+        table.set_header(vec![
+            "available animations",
+            "  ",
+            "selected animations",
+            "status",
+        ]);
         table.load_preset("││──╞═╪╡│    ┬┴┌┐└┘");
 
         let mut sorted_names = qc
             .sequences
-            .iter()
-            .map(|(name, _)| name.as_str())
+            .keys()
+            .map(|name| name.as_str())
             .collect::<Vec<&str>>();
         sorted_names.sort();
 
         for name in sorted_names {
             table.add_row(vec![
-                name.to_owned(),
-                "  ".to_owned(),
-                String::from_utf8(vec![b' '; name.len()]).unwrap(),
+                Cell::new(name),
+                Cell::new("  "),
+                Cell::new(String::from_utf8(vec![b' '; name.len()]).unwrap()),
+                status_cell(base_qc.merge_status(qc, name)),
             ]);
         }
         SequenceTable(table)
@@ -62,32 +249,59 @@ impl SequenceTable {
 
             let left_colum = cells.clone().next().unwrap().get_content();
             let middle_colum = cells.clone().nth(1).unwrap().get_content();
+            let status = cells.clone().nth(3).unwrap().clone();
             // no arrow == not selected, arrow == selected
-            if middle_colum == "  ".to_owned() && left_colum == name.as_ref().to_owned() {
+            if middle_colum == "  " && left_colum == name.as_ref() {
                 *row = Row::from(vec![
                     Cell::new(name.as_ref()),
                     Cell::new("->").fg(Color::Green),
                     Cell::new(name.as_ref()).fg(Color::Green),
+                    status,
                 ]);
-            } else if middle_colum == "->".to_owned() && left_colum == name.as_ref().to_owned() {
+            } else if middle_colum == "->" && left_colum == name.as_ref() {
+                *row = Row::from(vec![
+                    Cell::new(name.as_ref()),
+                    Cell::new("  "),
+                    Cell::new("  "),
+                    status,
+                ]);
+            }
+        }
+    }
+
+    // selects a sequence and shows label in the selected column, e.g. its option overrides
+    pub fn select<T: AsRef<str>>(&mut self, name: T, label: &str) {
+        for row in self.0.row_iter_mut() {
+            let cells = row.cell_iter();
+            if cells.clone().next().unwrap().get_content() == name.as_ref() {
+                let status = cells.clone().nth(3).unwrap().clone();
                 *row = Row::from(vec![
-                    name.as_ref().to_owned(),
-                    "  ".to_owned(),
-                    "  ".to_owned(),
+                    Cell::new(name.as_ref()),
+                    Cell::new("->").fg(Color::Green),
+                    Cell::new(label).fg(Color::Green),
+                    status,
                 ]);
             }
         }
     }
 
+    // deselects a sequence if it's selected
+    pub fn deselect<T: AsRef<str>>(&mut self, name: T) {
+        if self.get_selected().iter().any(|x| x == name.as_ref()) {
+            self.toggle_select(name);
+        }
+    }
+
     // deselects everything
     pub fn clear_selection(&mut self) {
         for row in self.0.row_iter_mut() {
             let mut cells = row.cell_iter();
-            if cells.clone().nth(1).unwrap().get_content() == "->".to_owned() {
+            if cells.clone().nth(1).unwrap().get_content() == "->" {
                 *row = Row::from(vec![
-                    cells.next().unwrap().get_content(),
-                    "  ".to_owned(),
-                    "  ".to_owned(),
+                    Cell::new(cells.next().unwrap().get_content()),
+                    Cell::new("  "),
+                    Cell::new("  "),
+                    cells.nth(2).unwrap().clone(),
                 ]);
             }
         }
@@ -100,9 +314,18 @@ impl SequenceTable {
         }
         println!("{}", self.0);
         println!(
-            "Enter names separated by a space (you can use globs)
+            "Enter names separated by a space (you can use globs, {}, {}, {} and {})
+Prefix a name with {} to deselect it, {} to keep the base's sequence too and let the game
+pick one at random, add {} to change its options
 Enter nothing to confirm, enter {} to exit, {} to clear
 {}",
+            style("re:/regex/").bold(),
+            style("act:ACTIVITY").bold(),
+            style(format!("fam:{}", activity::families().join("|"))).bold(),
+            style("uses:WEIGHTLIST").bold(),
+            style("!").bold(),
+            style("+").bold(),
+            style("{fps=24,fadein=base}").bold(),
             style("exit").bold(),
             style("clear").bold(),
             error,
@@ -116,56 +339,201 @@ Enter nothing to confirm, enter {} to exit, {} to clear
         self.0
             .row_iter()
             .map(|row| row.cell_iter())
-            .filter(|cell| cell.clone().nth(1).unwrap().get_content() == "->".to_owned())
+            .filter(|cell| cell.clone().nth(1).unwrap().get_content() == "->")
             .map(|cell| cell.clone().next().unwrap().get_content())
             .collect()
     }
 }
 
-pub fn process_input<T: AsRef<str>>(qc: &QC, input: T) -> Result<Vec<String>> {
-    let mut sequences = input
-        .as_ref()
-        .split_whitespace()
-        .map(|name| name.to_owned())
-        .collect::<Vec<String>>();
-
-    let all_sequences = qc
-        .sequences
-        .iter()
-        .map(|(name, _)| name.to_owned())
-        .collect::<Vec<String>>();
-
-    let prefixes = sequences
-        .iter()
-        .filter(|name| name.contains('*'))
-        .map(|name| name.to_owned())
-        .collect::<Vec<String>>();
-
-    for prefix in prefixes {
-        let pattern = Pattern::new(&prefix)?;
-        for name in all_sequences.iter() {
-            if pattern.matches(&name) {
-                sequences.push(name.to_owned());
-            }
-        }
+// colours the status so problems stand out before confirming
+fn status_cell(status: MergeStatus) -> Cell {
+    let cell = Cell::new(status.as_str());
+    match status {
+        MergeStatus::Replace => cell.fg(Color::Yellow),
+        MergeStatus::Append => cell.fg(Color::Green),
+        MergeStatus::ActivityMismatch | MergeStatus::NameCollision => cell.fg(Color::Red),
     }
+}
+
+// what to do with a sequence picked by process_input
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Selection {
+    // select it if it isn't selected, otherwise deselect it
+    Toggle(String),
+    // deselect it, `!pattern`
+    Deselect(String),
+    // select it and change its options when it's merged, `pattern{option=value,...}`
+    Override(String, Vec<OptionOverride>),
+    // select it and add it as well as the base's sequence instead of replacing it, the game
+    // picks one of them at random, `+pattern`, it can have options too
+    Alternate(String, Vec<OptionOverride>),
+}
+
+// processes the input line, each word is a selector:
+// `name`, `glob*`, `re:/regex/`, `act:ACTIVITY_GLOB*`, `fam:FAMILY`, `uses:WEIGHTLIST_GLOB*`
+// prefixing any of them with `!` deselects the matches instead of toggling them,
+// prefixing them with `+` selects them as alternates of the base's sequences
+// `{option=value,...}` after any of them selects the matches and changes their options
+pub fn process_input<T: AsRef<str>>(qc: &QC, input: T) -> Result<Vec<Selection>> {
+    let mut selections = Vec::new();
+    let mut not_found = Vec::new();
+
+    for word in input.as_ref().split_whitespace() {
+        let (deselect, word) = match word.strip_prefix('!') {
+            Some(word) => (true, word),
+            None => (false, word),
+        };
+        let (alternate, word) = match word.strip_prefix('+') {
+            Some(word) if !deselect => (true, word),
+            _ => (false, word),
+        };
+        let (selector, overrides) = split_overrides(word)?;
+        if deselect && overrides.is_some() {
+            bail!("Deselected sequences can't have options: `!{}`", word);
+        }
 
-    sequences.retain(|name| !name.contains('*'));
+        let names = match_selector(qc, selector)?;
+        if names.is_empty() && !is_pattern(selector) {
+            not_found.push(selector.to_owned());
+            continue;
+        }
+
+        selections.extend(names.into_iter().map(|name| match &overrides {
+            _ if deselect => Selection::Deselect(name),
+            _ if alternate => Selection::Alternate(name, overrides.clone().unwrap_or_default()),
+            Some(overrides) => Selection::Override(name, overrides.to_owned()),
+            None => Selection::Toggle(name),
+        }));
+    }
 
-    // checks if a key exists
-    if sequences
-        .iter()
-        .any(|name| !qc.sequences.contains_key(name.as_str()))
-    {
+    if !not_found.is_empty() {
         bail!(
             "Failed to find: {}",
-            sequences
+            not_found
                 .iter()
-                .filter(|&name| !qc.sequences.contains_key(name))
                 .map(|name| style(name).red().to_string())
                 .collect::<Vec<String>>()
                 .join(", ")
         );
     }
-    Ok(sequences)
+    Ok(selections)
+}
+
+// the option overrides of every sequence matched by `--override` selectors, in order
+pub fn process_overrides(
+    qc: &QC,
+    specs: &[String],
+) -> Result<HashMap<String, Vec<OptionOverride>>> {
+    let mut overrides: HashMap<String, Vec<OptionOverride>> = HashMap::new();
+    for spec in specs {
+        for selection in process_input(qc, spec)? {
+            match selection {
+                Selection::Override(name, options) => {
+                    overrides.entry(name).or_default().extend(options)
+                }
+                _ => bail!(
+                    "--override must be `SEQUENCES{{OPTION=VALUE,...}}`: `{}`",
+                    spec
+                ),
+            }
+        }
+    }
+    Ok(overrides)
+}
+
+// splits `selector{option=value,...}` into the selector and the overrides,
+// only the last `{` counts so regexes can still use `{n}`
+fn split_overrides(word: &str) -> Result<(&str, Option<Vec<OptionOverride>>)> {
+    let (selector, options) = match word.strip_suffix('}').zip(word.rfind('{')) {
+        Some((word, start)) => (&word[..start], &word[start + 1..]),
+        None => return Ok((word, None)),
+    };
+    let overrides = options
+        .split(',')
+        .map(OptionOverride::parse)
+        .collect::<Result<Vec<OptionOverride>>>()?;
+    Ok((selector, Some(overrides)))
+}
+
+// true if the selector can match any number of sequences, including none
+fn is_pattern(selector: &str) -> bool {
+    selector.contains('*')
+        || selector.starts_with("re:")
+        || selector.starts_with("act:")
+        || selector.starts_with("fam:")
+        || selector.starts_with("uses:")
+}
+
+// returns the sorted names of the sequences that match a single selector
+fn match_selector(qc: &QC, selector: &str) -> Result<Vec<String>> {
+    let mut names = if let Some(regex) = selector.strip_prefix("re:") {
+        let regex = regex
+            .strip_prefix('/')
+            .and_then(|regex| regex.strip_suffix('/'))
+            .with_context(|| format!("Regex must be wrapped in slashes: `{}`", selector))?;
+        let regex = Regex::new(regex)?;
+        qc.sequences
+            .keys()
+            .filter(|name| regex.is_match(name))
+            .map(|name| name.to_owned())
+            .collect::<Vec<String>>()
+    } else if let Some(activity) = selector.strip_prefix("act:") {
+        let pattern = Pattern::new(activity)?;
+        qc.sequences
+            .values()
+            .filter(|seq| {
+                let activities = seq.activities();
+                // `act:*` still matches sequences without an activity
+                activities.iter().any(|activity| pattern.matches(activity))
+                    || activities.is_empty() && pattern.matches("")
+            })
+            .map(|seq| seq.name.to_owned())
+            .collect::<Vec<String>>()
+    } else if let Some(family) = selector.strip_prefix("fam:") {
+        let family = family.to_lowercase();
+        if !activity::families().contains(&family.as_str()) {
+            bail!(
+                "`{}` isn't an activity family, use one of: {}",
+                family,
+                activity::families().join(", ")
+            );
+        }
+        qc.sequences
+            .values()
+            .filter(|seq| {
+                seq.activities()
+                    .iter()
+                    .any(|activity| activity::family(activity) == Some(family.as_str()))
+            })
+            .map(|seq| seq.name.to_owned())
+            .collect::<Vec<String>>()
+    } else if let Some(weightlist) = selector.strip_prefix("uses:") {
+        let pattern = Pattern::new(weightlist)?;
+        qc.sequences
+            .values()
+            .filter(|seq| {
+                pattern.matches(&seq.weightlist)
+                    // the $animation it uses can have its own $weightlist
+                    || seq.uses_animation
+                        && qc
+                            .animations
+                            .get(&seq.smd)
+                            .is_some_and(|anim| pattern.matches(&anim.weightlist))
+            })
+            .map(|seq| seq.name.to_owned())
+            .collect::<Vec<String>>()
+    } else if selector.contains('*') {
+        let pattern = Pattern::new(selector)?;
+        qc.sequences
+            .keys()
+            .filter(|name| pattern.matches(name))
+            .map(|name| name.to_owned())
+            .collect::<Vec<String>>()
+    } else if qc.sequences.contains_key(selector) {
+        vec![selector.to_owned()]
+    } else {
+        Vec::new()
+    };
+    names.sort();
+    Ok(names)
 }
@@ -1,2 +1,4 @@
 pub mod cli;
+pub mod output;
 pub mod qc;
+pub mod smd;
@@ -1,13 +1,22 @@
-mod cli;
-mod qc;
-
-use anyhow::{Context, Result};
-use cli::{Opt, SequenceTable};
+use anyhow::{bail, Context, Result};
+use comfy_table::Table;
 use console::{style, Term};
-use qc::QC;
-use std::fs;
-use std::fs::File;
-use std::io::{BufRead, BufReader, BufWriter, Write};
+use qcmerger_rust::cli::{self, Command, Conflict, Opt, Selection, SequenceTable};
+use qcmerger_rust::output::{
+    self, copy_donor_smds, copy_smds, copy_smds_in_place, write_qc, Staging,
+};
+use qcmerger_rust::qc::activity;
+use qcmerger_rust::qc::animation::Animation;
+use qcmerger_rust::qc::event::Event;
+use qcmerger_rust::qc::options::OptionOverride;
+use qcmerger_rust::qc::path;
+use qcmerger_rust::qc::placement::Placement;
+use qcmerger_rust::qc::sequence::Sequence;
+use qcmerger_rust::qc::weightlist::WeightDiff;
+use qcmerger_rust::qc::QC;
+use qcmerger_rust::smd::resample::{conformed_frame_count, DEFAULT_FPS};
+use qcmerger_rust::smd::{self, retarget::BoneMap, Smd};
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 use std::process::exit;
 use structopt::StructOpt;
@@ -15,10 +24,29 @@ use structopt::StructOpt;
 fn main() -> Result<()> {
     let opt: Opt = Opt::from_args();
 
-    let qc_file = QC::new(&opt.file)?;
-    let qc_base = QC::new(&opt.base_file)?;
+    match &opt.cmd {
+        Some(Command::Restore { backup }) => return restore(&opt, backup.as_deref()),
+        Some(cmd) => return edit_smd(cmd),
+        None => {}
+    }
+    let (file, base_file) = opt.qc_files();
+
+    let mut qc_file = QC::new(&file)?;
+    let qc_base = QC::new(&base_file)?;
+    if opt.placement == Placement::Qci {
+        qc_base.check_qci()?;
+    }
+    let bone_map = match &opt.bone_map {
+        Some(bone_map) => BoneMap::new(bone_map)?,
+        None => BoneMap::default(),
+    };
+    let skeleton = smd::skeleton(&qc_base);
+    // the options that are changed on each merged sequence
+    let mut overrides = cli::process_overrides(&qc_file, &opt.overrides)?;
+    // the sequences that are added as well as the base's sequence instead of replacing it
+    let mut alternates = HashSet::new();
 
-    let mut table = SequenceTable::from(&qc_file);
+    let mut table = SequenceTable::from(&qc_file, &qc_base);
     let mut error = String::new();
     let term = Term::stdout();
 
@@ -47,15 +75,63 @@ fn main() -> Result<()> {
             }
         }
         .iter()
-        .for_each(|name| table.toggle_select(name));
+        .for_each(|selection| match selection {
+            Selection::Toggle(name) => {
+                alternates.remove(name);
+                table.toggle_select(name)
+            }
+            Selection::Deselect(name) => {
+                alternates.remove(name);
+                table.deselect(name)
+            }
+            Selection::Override(name, new_options) | Selection::Alternate(name, new_options) => {
+                if matches!(selection, Selection::Alternate(..)) {
+                    alternates.insert(name.to_owned());
+                }
+                let options = overrides.entry(name.to_owned()).or_default();
+                options.extend(new_options.iter().cloned());
+                let label = override_label(name, options);
+                if alternates.contains(name) {
+                    table.select(name, &format!("+{}", label));
+                } else {
+                    table.select(name, &label);
+                }
+            }
+        });
     }
 
-    let selected_sequences = table
+    let mut selected_sequences = table
         .get_selected()
         .iter()
         .map(|name| name.to_owned())
         .collect::<Vec<String>>();
 
+    // the alternates are renamed so they're appended, new name -> name in both qcs
+    let mut alternate_names = HashMap::new();
+    for seq in selected_sequences.iter_mut() {
+        if !alternates.contains(seq.as_str()) {
+            continue;
+        }
+        if !qc_base.sequences.contains_key(seq.as_str()) {
+            println!(
+                "The base doesn't have {}, it's appended as it is",
+                style(&seq).yellow()
+            );
+            continue;
+        }
+        let name = qc_file.make_alternate(&qc_base, &seq)?;
+        println!(
+            "{} is added as {}, the game picks it or the base's at random",
+            style(&seq).green(),
+            style(&name).green()
+        );
+        if let Some(options) = overrides.remove(seq.as_str()) {
+            overrides.insert(name.to_owned(), options);
+        }
+        alternate_names.insert(name.to_owned(), seq.to_owned());
+        *seq = name;
+    }
+
     println!(
         "Transferring: {}",
         selected_sequences
@@ -64,32 +140,35 @@ fn main() -> Result<()> {
             .collect::<Vec<String>>()
             .join(", ")
     );
+    check_activities(&qc_file, &qc_base, &selected_sequences);
 
     // sequences to add to base qc file
     let mut layers = Vec::new();
     // finds any `addlayer` and `blendlayer`
     for seq in &selected_sequences {
-        if &qc_file.sequences.contains_key(seq) == &true {
-            if !&qc_file.sequences[seq].layer.is_empty() {
-                for layer in &qc_file.sequences[seq].layer {
-                    // push if it's not already selected and not in layers
-                    if !selected_sequences.contains(&layer) && !layers.contains(layer) {
-                        layers.push(layer.to_owned());
-                    }
+        if qc_file.sequences.contains_key(seq) && !qc_file.sequences[seq].layer.is_empty() {
+            for layer in &qc_file.sequences[seq].layer {
+                // push if it's not already selected and not in layers
+                if !selected_sequences.contains(layer) && !layers.contains(layer) {
+                    layers.push(layer.to_owned());
                 }
             }
         }
     }
 
+    // smds that would overwrite a different base smd the merged qc still uses are moved to
+    // their own folder, --in-place keeps every file in the base's folder
+    let kept = qc_base.get_smds_except(&selected_sequences);
+    let mut relocated = output::relocate_collisions(&mut qc_file, &qc_base, |smd| {
+        opt.in_place || path::contains(&kept, smd)
+    })?;
+
     let mut new_qc = qc_base.clone();
-    let mut new_qc_data = BufReader::new(
-        File::open(&opt.base_file)
-            .with_context(|| format!("Failed to read: {}", &opt.base_file.display()))?,
-    )
-    .lines()
-    .filter_map(|line| line.ok())
-    .map(|line| line.trim().to_owned())
-    .collect::<Vec<String>>();
+    new_qc.placement = opt.placement.clone();
+    new_qc.provenance = opt
+        .provenance
+        .clone()
+        .map(|recipe| recipe.unwrap_or_else(output::timestamp));
 
     // might be used later to rename commands to prevent name collision
     let mut appended_weightlists = Vec::new();
@@ -97,6 +176,111 @@ fn main() -> Result<()> {
     let mut appended_sequences = Vec::new();
     let mut replaced_sequences = Vec::new();
 
+    // same-named $animations are compared, the donor's is only used if it's different
+    let donor_sequences = selected_sequences
+        .iter()
+        .chain(layers.iter())
+        .cloned()
+        .collect::<Vec<String>>();
+    // the base $animations that are replaced with the donor's
+    let mut replaced_animations = HashSet::new();
+    // the $animations and $weightlists the donor's replace, for the qci placement's warning
+    let mut replaced_blocks = Vec::new();
+    for name in qc_file.used_animations(&donor_sequences) {
+        let (animation, base_animation) =
+            match (qc_file.animations.get(&name), qc_base.animations.get(&name)) {
+                (Some(animation), Some(base_animation)) => (animation, base_animation),
+                _ => continue,
+            };
+        if base_animation.same_as(animation) {
+            println!(
+                "{} is the same in both, the base's is used",
+                style(&name).green()
+            );
+            continue;
+        }
+        let mode = match opt.animation_conflicts {
+            Conflict::Ask => ask_animation_conflict(&term, base_animation, animation)?,
+            mode => mode,
+        };
+        match mode {
+            Conflict::Rename => {
+                let new_name = free_name(&name, |new_name| {
+                    [&qc_file, &qc_base].iter().any(|qc| {
+                        qc.animations.contains_key(new_name) || qc.sequences.contains_key(new_name)
+                    })
+                });
+                qc_file.rename_animation(&name, &new_name)?;
+                println!(
+                    "The donor's {} is added as {}",
+                    style(&name).yellow(),
+                    style(&new_name).green()
+                );
+            }
+            Conflict::Donor => {
+                replaced_animations.insert(name.to_owned());
+                replaced_blocks.push(name.to_owned());
+                println!(
+                    "The donor's {} replaces the base's, the base's sequences use it too",
+                    style(&name).yellow()
+                );
+            }
+            _ => println!(
+                "The base's {} is used, the donor's is different",
+                style(&name).yellow()
+            ),
+        }
+    }
+
+    // same-named $weightlists are compared, the donor's is only used if the weights differ
+    for name in qc_file.used_weightlists(&donor_sequences) {
+        let diff = match (
+            qc_file.weightlists.get(&name),
+            qc_base.weightlists.get(&name),
+        ) {
+            (Some(weightlist), Some(base_weightlist)) => base_weightlist.diff(weightlist),
+            _ => continue,
+        };
+        if diff.is_empty() {
+            println!(
+                "{} has the same weights in both, the base's is used",
+                style(&name).green()
+            );
+            continue;
+        }
+        let mode = match opt.weightlist_conflicts {
+            Conflict::Ask => ask_weightlist_conflict(&term, &name, &diff)?,
+            mode => mode,
+        };
+        match mode {
+            Conflict::Rename => {
+                let new_name = free_name(&name, |new_name| {
+                    qc_file.weightlists.contains_key(new_name)
+                        || qc_base.weightlists.contains_key(new_name)
+                });
+                qc_file.rename_weightlist(&name, &new_name)?;
+                println!(
+                    "The donor's {} is added as {}",
+                    style(&name).yellow(),
+                    style(&new_name).green()
+                );
+            }
+            Conflict::Donor => {
+                new_qc.replace_weightlist(&qc_file, &name)?;
+                appended_weightlists.push(name.to_owned());
+                replaced_blocks.push(name.to_owned());
+                println!(
+                    "The donor's {} replaces the base's, the base's sequences use it too",
+                    style(&name).yellow()
+                );
+            }
+            _ => println!(
+                "The base's {} is used, the donor's weights are different",
+                style(&name).yellow()
+            ),
+        }
+    }
+
     // replaces sequences from base_qc with those from qc_file
     // also appends $weightlist and $animation
     for seq in &selected_sequences {
@@ -105,149 +289,850 @@ fn main() -> Result<()> {
             layers.push(seq.to_owned());
             continue;
         }
-        let other_sequence = new_qc.replace_sequence(&qc_file, &seq, &mut new_qc_data)?;
+        let other_sequence = new_qc.replace_sequence(&qc_file, seq)?;
         replaced_sequences.push(other_sequence.name.clone());
-        if !other_sequence.weightlist.is_empty() // if it uses a $weightlist
-            // if the $weightlist isn't already in new_qc
-            && !new_qc.weightlists.contains_key(&other_sequence.weightlist)
-        {
-            appended_weightlists.push(new_qc.append_weightlist(
-                &qc_file,
-                &other_sequence.weightlist,
-                &mut new_qc_data,
-            )?);
-        }
-        if other_sequence.uses_animation {
-            let other_animation =
-                new_qc.append_animation(&qc_file, &other_sequence.smd, &mut new_qc_data)?;
-            appended_animations.push(other_animation.name.clone());
-            if !other_animation.weightlist.is_empty()
-                && !new_qc.weightlists.contains_key(&other_animation.weightlist)
-            {
-                appended_weightlists.push(new_qc.append_weightlist(
-                    &qc_file,
-                    &other_animation.weightlist,
-                    &mut new_qc_data,
-                )?);
+        merge_dependencies(
+            &mut new_qc,
+            &qc_file,
+            &other_sequence,
+            &mut replaced_animations,
+            &mut appended_animations,
+            &mut appended_weightlists,
+        )?;
+    }
+
+    // only appended blocks go in the qci, the replaced ones are changed in the base qc
+    replaced_blocks.extend(replaced_sequences.iter().cloned());
+    if opt.placement == Placement::Qci && !replaced_blocks.is_empty() {
+        println!(
+            "{} {} replaced in the base qc itself, only appended blocks go in the qci",
+            style(replaced_blocks.join(", ")).yellow(),
+            if replaced_blocks.len() == 1 {
+                "is"
+            } else {
+                "are"
             }
-        }
+        );
     }
 
     // appends $sequence and others from qc_file to base_qc
     for seq in &layers {
-        let other_sequence = new_qc.append_sequence(&qc_file, &seq, &mut new_qc_data)?;
+        let other_sequence = new_qc.append_sequence(&qc_file, seq)?;
         appended_sequences.push(other_sequence.name.clone());
-        if !other_sequence.weightlist.is_empty()
-            && !new_qc.weightlists.contains_key(&other_sequence.weightlist)
-        {
-            appended_weightlists.push(new_qc.append_weightlist(
-                &qc_file,
-                &other_sequence.weightlist,
-                &mut new_qc_data,
-            )?);
-        }
-        if other_sequence.uses_animation {
-            let other_animation =
-                new_qc.append_animation(&qc_file, &other_sequence.smd, &mut new_qc_data)?;
-            appended_animations.push(other_animation.name.clone());
-            if !other_animation.weightlist.is_empty()
-                && !new_qc.weightlists.contains_key(&other_animation.weightlist)
-            {
-                appended_weightlists.push(new_qc.append_weightlist(
-                    &qc_file,
-                    &other_animation.weightlist,
-                    &mut new_qc_data,
-                )?);
-            }
+        merge_dependencies(
+            &mut new_qc,
+            &qc_file,
+            &other_sequence,
+            &mut replaced_animations,
+            &mut appended_animations,
+            &mut appended_weightlists,
+        )?;
+    }
+
+    // the copied $weightlists use the donor's bone names
+    if !bone_map.is_empty() {
+        for weightlist in &appended_weightlists {
+            new_qc.rename_weightlist_bones(weightlist, &bone_map)?;
         }
     }
 
-    #[cfg(debug_assertions)]
-    new_qc_data.iter().for_each(|line| println!("{}", line));
+    // the smds of replaced sequences that are resampled, and the fps they're resampled from and to
+    let mut conformed = HashMap::new();
+    // the sequences that get the base's fps once their smds are resampled, and the fps
+    // they're resampled from and to
+    let mut conformed_sequences = Vec::new();
+    if opt.conform_fps {
+        for name in &replaced_sequences {
+            let (seq, base_seq) = (&qc_file.sequences[name], &qc_base.sequences[name]);
+            let (fps, base_fps) = (
+                seq.options.fps.unwrap_or(DEFAULT_FPS),
+                base_seq.options.fps.unwrap_or(DEFAULT_FPS),
+            );
+            if fps == base_fps {
+                continue;
+            }
+            if overrides
+                .get(name)
+                .is_some_and(|options| options.iter().any(|option| option.key == "fps"))
+            {
+                println!(
+                    "{} has its fps changed, its fps wasn't conformed",
+                    style(name).yellow()
+                );
+                continue;
+            }
+            if seq.uses_animation || seq.smd.is_empty() {
+                println!(
+                    "{} uses an $animation, its fps wasn't conformed",
+                    style(name).yellow()
+                );
+                continue;
+            }
+            if smd::is_dmx(&seq.smd) {
+                println!(
+                    "{} uses a dmx, only smds can be resampled, its fps wasn't conformed",
+                    style(name).yellow()
+                );
+                continue;
+            }
+            let smd = PathBuf::from(seq.smd_path());
+            match conformed.get(&smd) {
+                Some(&other) if other != (fps, base_fps) => bail!(
+                    "{} is used by sequences that need different fps, it can't be conformed",
+                    smd.display()
+                ),
+                _ => {}
+            }
+            conformed.insert(smd, (fps, base_fps));
+            conformed_sequences.push((name.to_owned(), fps, base_fps));
+        }
 
-    // if output folder already exists
-    if opt.output.exists() {
-        println!("Output folder already exists, overwrite it? [y/n]");
+        // resampling a file that other blocks of the merged qc use, or one that's already in
+        // the base qc's folder, would change it for them too, so those sequences get a copy
+        let names = conformed_sequences
+            .iter()
+            .map(|(name, _, _)| name.to_owned())
+            .collect::<Vec<String>>();
+        let shared = qc_base
+            .get_smds_except(&replaced_sequences)
+            .into_iter()
+            .chain(
+                replaced_sequences
+                    .iter()
+                    .chain(&appended_sequences)
+                    .filter(|name| !names.contains(name))
+                    .filter_map(|name| qc_file.sequences.get(name))
+                    .filter(|seq| !seq.uses_animation && !seq.smd.is_empty())
+                    .map(|seq| PathBuf::from(seq.smd_path())),
+            )
+            .chain(
+                appended_animations
+                    .iter()
+                    .filter_map(|name| qc_file.animations.get(name))
+                    .map(|anim| PathBuf::from(anim.smd_path())),
+            )
+            .collect::<Vec<PathBuf>>();
+        let base_dir = base_file.parent().unwrap_or_else(|| Path::new(""));
+        let stem = file.file_stem().unwrap_or_default().to_string_lossy();
+        for (name, _, base_fps) in &conformed_sequences {
+            let smd = PathBuf::from(qc_file.sequences[name].smd_path());
+            let in_base_dir = opt.in_place && path::find(base_dir, &smd).is_some();
+            if !in_base_dir && !path::contains(&shared, &smd) {
+                continue;
+            }
+            let to = output::relocation_path(
+                &format!("{}_{}fps", stem, base_fps),
+                &smd.to_string_lossy(),
+            );
+            qc_file.relocate_sequence_smd(name.as_str(), to.as_str());
+            let source = relocated.get(&smd).cloned().unwrap_or_else(|| smd.clone());
+            relocated.insert(PathBuf::from(&to), source);
+            if let Some(fps) = conformed.remove(&smd) {
+                conformed.insert(PathBuf::from(&to), fps);
+            }
+        }
+    }
 
-        loop {
-            let input = term.read_line()?;
-            match input.to_lowercase().as_str() {
-                "y" | "yes" => {
-                    fs::remove_dir_all(&opt.output)
-                        .with_context(|| format!("Failed to remove {}", opt.output.display()))?;
-                    break;
-                }
-                "n" | "no" => break,
+    // the base's sound events are moved to the same point of the donor animation
+    if opt.keep_base_events {
+        for name in &replaced_sequences {
+            let mut events = qc_base.sequences[name]
+                .options
+                .events
+                .iter()
+                .filter(|event| event.is_sound())
+                .cloned()
+                .collect::<Vec<Event>>();
+            if events.is_empty() {
+                continue;
+            }
+            let (base_frames, frames) = match (
+                qc_base.frame_count(name, &HashMap::new()),
+                qc_file.frame_count(name, &relocated),
+            ) {
+                (Some(base_frames), Some(frames)) => (base_frames, frames),
                 _ => {
-                    eprintln!("Invalid input")
+                    println!(
+                        "{}'s frames couldn't be counted, it keeps its own events",
+                        style(name).yellow()
+                    );
+                    continue;
+                }
+            };
+            // the donor smd is resampled later
+            let seq = &qc_file.sequences[name];
+            let frames = match conformed.get(&PathBuf::from(seq.smd_path())) {
+                Some(&(fps, new_fps)) if !seq.uses_animation => {
+                    conformed_frame_count(frames, fps, new_fps)
                 }
+                _ => frames,
+            };
+            for event in events.iter_mut() {
+                event.rescale(base_frames, frames);
             }
+            new_qc.replace_sound_events(name, &events)?;
+            println!(
+                "{} keeps the base's sound events, rescaled from {} to {} frames",
+                style(name).green(),
+                base_frames,
+                frames
+            );
         }
     }
-    // creates output dir
-    fs::create_dir_all(&opt.output)?;
 
-    // copies base .smd files to output dir
-    copy_smds(&qc_base.get_smds(), &opt.base_file, &opt.output)?;
+    // the options picked with `name{option=value}` or --override
+    for name in replaced_sequences.iter().chain(appended_sequences.iter()) {
+        let options = match overrides.get(name) {
+            Some(options) => options,
+            None => continue,
+        };
+        let base_name = alternate_names.get(name).unwrap_or(name);
+        let base = qc_base.sequences.get(base_name).map(|seq| &seq.options);
+        new_qc
+            .override_options(name, options, base)
+            .with_context(|| format!("Failed to change the options of {}", name))?;
+        println!(
+            "{} uses {}",
+            style(name).green(),
+            override_label(name, options)
+        );
+    }
 
-    // copies replaced and appened smds to output dir
+    #[cfg(debug_assertions)]
+    new_qc
+        .document
+        .lines()
+        .iter()
+        .for_each(|line| println!("{}", line));
+
+    // replaced and appened smds
     let new_blocks = replaced_sequences
         .iter()
         .chain(appended_sequences.iter())
         .chain(appended_animations.iter())
         .collect::<Vec<&String>>();
+    // the new blocks can be in a different $pushd folder than in qc_file
+    new_qc.rebase_smds(
+        &qc_file,
+        &new_blocks
+            .iter()
+            .map(|&name| name.to_owned())
+            .collect::<Vec<String>>(),
+    );
 
-    let new_seq_smds = new_blocks
+    let mut new_seq_smds = new_blocks
         .iter()
         .filter(|&&name| qc_file.sequences.contains_key(name))
         .map(|&name| &qc_file.sequences[name])
         .filter(|seq| !seq.uses_animation && !seq.smd.is_empty())
-        .map(|seq| PathBuf::from(&seq.smd))
+        .map(|seq| PathBuf::from(seq.smd_path()))
         .collect::<Vec<PathBuf>>();
 
-    let new_anim_smds = new_blocks
+    let mut new_anim_smds = new_blocks
         .iter()
         .filter(|&&name| qc_file.animations.contains_key(name))
         .map(|&name| &qc_file.animations[name])
         .filter(|anim| !anim.smd.is_empty())
-        .map(|anim| PathBuf::from(&anim.smd))
+        .map(|anim| PathBuf::from(anim.smd_path()))
+        .collect::<Vec<PathBuf>>();
+
+    let mut base_smds = qc_base.get_smds();
+    // makes smds with the same bytes share one file
+    if opt.dedup {
+        let sources = output::smd_sources(&base_smds, &HashMap::new(), &base_file)
+            .into_iter()
+            .chain(output::smd_sources(&new_seq_smds, &relocated, &file))
+            .chain(output::smd_sources(&new_anim_smds, &relocated, &file))
+            // resampled smds won't have the same bytes anymore
+            .filter(|(smd, _)| !conformed.contains_key(smd))
+            .collect::<Vec<(PathBuf, PathBuf)>>();
+        let duplicates = output::find_duplicates(&sources)?;
+        for (duplicate, original) in &duplicates {
+            new_qc.relocate_smd_in(duplicate.to_string_lossy(), original.to_string_lossy());
+            println!(
+                "{} is identical to {}, sharing it",
+                duplicate.display(),
+                style(original.display()).green()
+            );
+        }
+        base_smds.retain(|smd| !duplicates.contains_key(smd));
+        new_seq_smds.retain(|smd| !duplicates.contains_key(smd));
+        new_anim_smds.retain(|smd| !duplicates.contains_key(smd));
+    }
+
+    // only the base smds the merged qc still uses are copied
+    let referenced = new_qc.get_smds();
+    let mut orphaned = base_smds
+        .iter()
+        .filter(|smd| !path::contains(&referenced, smd))
+        .cloned()
         .collect::<Vec<PathBuf>>();
+    orphaned.sort();
+    orphaned.dedup();
+    base_smds.retain(|smd| path::contains(&referenced, smd));
+    // a donor smd at the same path takes its place, relocate_collisions moved the others
+    base_smds.retain(|smd| !path::contains(new_seq_smds.iter().chain(&new_anim_smds), smd));
+    print_orphaned(&orphaned, "no longer used by the merged qc");
+
+    // every file that will be copied has to exist
+    let missing = qc_base
+        .missing_smds()
+        .into_iter()
+        .filter(|(_, smd, _)| path::contains(&base_smds, smd))
+        .chain(
+            qc_file
+                .missing_smds()
+                .into_iter()
+                .filter(|(name, _, _)| new_blocks.contains(&name))
+                // relocated smds are copied from their original path, which was found
+                .filter(|(_, smd, _)| !path::contains(relocated.keys(), smd)),
+        )
+        .collect::<Vec<(String, String, Vec<PathBuf>)>>();
+    if !missing.is_empty() {
+        bail!(
+            "Failed to find these files or $animations, nothing was written:\n{}",
+            missing
+                .iter()
+                .map(|(name, smd, tried)| format!(
+                    "  {}: `{}`, tried {}",
+                    style(name).red(),
+                    smd,
+                    tried
+                        .iter()
+                        .map(|path| path.display().to_string())
+                        .collect::<Vec<String>>()
+                        .join(", ")
+                ))
+                .collect::<Vec<String>>()
+                .join("\n")
+        );
+    }
+
+    let new_sources = output::smd_sources(&new_seq_smds, &relocated, &file)
+        .into_iter()
+        .chain(output::smd_sources(&new_anim_smds, &relocated, &file))
+        .collect::<Vec<(PathBuf, PathBuf)>>();
+    check_skeleton(&skeleton, &new_sources, &bone_map);
+
+    let qci_file = base_file.with_file_name(new_qc.qci_name());
+
+    if opt.in_place {
+        // the base qc might already $include it from an earlier merge
+        if new_qc.output_lines().1.is_some() && qci_file.exists() {
+            bail!(
+                "{} already exists, remove it or use a different --placement",
+                qci_file.display()
+            );
+        }
+        // only the new smds are copied, next to the base qc
+        let new_smds = new_seq_smds
+            .into_iter()
+            .chain(new_anim_smds)
+            .collect::<Vec<PathBuf>>();
+        let copied = copy_smds_in_place(
+            &new_smds,
+            &relocated,
+            &file,
+            &base_file,
+            &qc_base.get_smds(),
+        )?;
+        print_relocated(&new_smds, &relocated);
+        if !bone_map.is_empty() {
+            // only the copies are retargeted, the files that were already in the base qc's
+            // folder belong to the user, even if they have the same bytes as the donor's
+            let base_dir = base_file.parent().unwrap_or_else(|| Path::new(""));
+            print_skipped_dmxs(
+                &output::retarget_smds(&copied, &relocated, &file, base_dir, &bone_map, &skeleton)?,
+                "retargeted",
+            );
+        }
+        // only this merge's copies are resampled, the sequences that shared a file got their own
+        let base_dir = base_file.parent().unwrap_or_else(|| Path::new(""));
+        let mut skipped = conformed
+            .keys()
+            .filter(|smd| !path::contains(&copied, smd))
+            .cloned()
+            .collect::<Vec<PathBuf>>();
+        conformed.retain(|smd, _| !path::contains(&skipped, smd));
+        skipped.extend(output::resample_smds(
+            &conformed, &relocated, &file, base_dir,
+        )?);
+        set_conformed_fps(&mut new_qc, &qc_file, &conformed_sequences, &skipped)?;
+
+        let (qc_lines, qci_lines) = new_qc.output_lines();
+        if output::same_qc(&base_file, &qc_lines) {
+            println!("The base qc didn't change, it wasn't backed up");
+        } else {
+            let backup = output::backup_file(&base_file)?;
+            write_qc(&base_file, &qc_lines)?;
+            print_pruned(&output::prune_backups(&base_file, opt.keep_backups)?);
+            if backup.exists() {
+                println!("Base qc was backed up to {}", backup.display());
+            }
+        }
+        if let Some(qci_lines) = qci_lines {
+            write_qc(&qci_file, &qci_lines)?;
+            println!("Merged blocks were written to {}", qci_file.display());
+        }
+        return Ok(());
+    }
+
+    // if output folder already exists
+    if opt.output.exists() && !opt.merge {
+        println!("Output folder already exists, overwrite it? A backup will be kept [y/n]");
+
+        loop {
+            let input = term.read_line()?;
+            match input.to_lowercase().as_str() {
+                "y" | "yes" => break,
+                "n" | "no" => {
+                    println!("Nothing was written, use --merge to keep the existing files");
+                    exit(0);
+                }
+                _ => {
+                    eprintln!("Invalid input")
+                }
+            }
+        }
+    }
+    // everything is written here first, the output folder is only touched at the end
+    let staging = Staging::new(&opt.output)?;
 
-    copy_smds(&new_seq_smds, &opt.file, &opt.output)?;
-    copy_smds(&new_anim_smds, &opt.file, &opt.output)?;
+    // copies base .smd files to output dir
+    copy_smds(&base_smds, &base_file, staging.path())?;
+
+    // copies replaced and appened smds to output dir
+    copy_donor_smds(&new_seq_smds, &relocated, &file, staging.path())?;
+    copy_donor_smds(&new_anim_smds, &relocated, &file, staging.path())?;
+    print_relocated(&new_seq_smds, &relocated);
+    print_relocated(&new_anim_smds, &relocated);
+    if !bone_map.is_empty() {
+        for smds in &[&new_seq_smds, &new_anim_smds] {
+            print_skipped_dmxs(
+                &output::retarget_smds(
+                    smds,
+                    &relocated,
+                    &file,
+                    staging.path(),
+                    &bone_map,
+                    &skeleton,
+                )?,
+                "retargeted",
+            );
+        }
+    }
+    let skipped = output::resample_smds(&conformed, &relocated, &file, staging.path())?;
+    set_conformed_fps(&mut new_qc, &qc_file, &conformed_sequences, &skipped)?;
 
     // creates output qc
-    let output_qc = File::create(&opt.output.join(&opt.base_file.file_name().unwrap()))?;
+    let (qc_lines, qci_lines) = new_qc.output_lines();
+    write_qc(
+        staging.path().join(base_file.file_name().unwrap()),
+        &qc_lines,
+    )?;
+    if let Some(qci_lines) = qci_lines {
+        write_qc(
+            staging.path().join(qci_file.file_name().unwrap()),
+            &qci_lines,
+        )?;
+    }
 
-    // writes to the file
-    let mut write = BufWriter::new(output_qc);
-    for line in new_qc_data {
-        write.write_all((line + "\n").as_bytes())?;
+    let backup = if opt.merge {
+        staging.merge()?
+    } else {
+        staging.replace()?
+    };
+    print_pruned(&output::prune_backups(&opt.output, opt.keep_backups)?);
+    if let Some(backup) = backup.filter(|backup| backup.exists()) {
+        println!("Previous output was backed up to {}", backup.display());
+    }
+    if opt.merge {
+        print_orphaned(
+            &output::unreferenced_smds(&opt.output, &referenced)?,
+            "left in the output folder but not used by the merged qc",
+        );
     }
+    Ok(())
+}
 
+// merges the $weightlist and $animation a merged donor sequence uses into new_qc, unless
+// new_qc already has them
+// same-named $animations were compared before, the base's is used if it's kept
+fn merge_dependencies(
+    new_qc: &mut QC,
+    qc_file: &QC,
+    seq: &Sequence,
+    replaced_animations: &mut HashSet<String>,
+    appended_animations: &mut Vec<String>,
+    appended_weightlists: &mut Vec<String>,
+) -> Result<()> {
+    if !seq.weightlist.is_empty() && !new_qc.weightlists.contains_key(&seq.weightlist) {
+        appended_weightlists.push(new_qc.append_weightlist(qc_file, &seq.weightlist)?);
+    }
+    let animation = if !seq.uses_animation {
+        None
+    } else if replaced_animations.remove(&seq.smd) {
+        Some(new_qc.replace_animation(qc_file, &seq.smd)?)
+    } else if !new_qc.animations.contains_key(&seq.smd)
+        // an unresolved reference is reported with the missing files
+        && qc_file.animations.contains_key(&seq.smd)
+    {
+        Some(new_qc.append_animation(qc_file, &seq.smd)?)
+    } else {
+        None
+    };
+    if let Some(animation) = animation {
+        appended_animations.push(animation.name.clone());
+        if !animation.weightlist.is_empty()
+            && !new_qc.weightlists.contains_key(&animation.weightlist)
+        {
+            appended_weightlists.push(new_qc.append_weightlist(qc_file, &animation.weightlist)?);
+        }
+    }
     Ok(())
 }
 
-fn copy_smds<P: AsRef<Path>>(smds: &Vec<PathBuf>, qc_file: P, output_dir: P) -> Result<()> {
-    let mut qc_path_parent = qc_file.as_ref().to_path_buf().clone();
-    qc_path_parent.pop();
-    let x = smds
-        .iter()
-        .map(|smd| PathBuf::from(&qc_path_parent.join(smd)))
-        .collect::<Vec<PathBuf>>();
-    let y = smds
-        .iter()
-        .map(|smd| PathBuf::from(output_dir.as_ref().join(smd)))
-        .collect::<Vec<PathBuf>>();
+// the first of `name_donor`, `name_donor2`, ... that isn't taken
+fn free_name<F: Fn(&str) -> bool>(name: &str, taken: F) -> String {
+    (1..)
+        .map(|i| match i {
+            1 => format!("{}_donor", name),
+            i => format!("{}_donor{}", name, i),
+        })
+        .find(|new_name| !taken(new_name))
+        .unwrap()
+}
+
+// a sequence's name with its option overrides, `name{option=value,...}`
+fn override_label(name: &str, options: &[OptionOverride]) -> String {
+    if options.is_empty() {
+        return name.to_owned();
+    }
+    format!(
+        "{}{{{}}}",
+        name,
+        options
+            .iter()
+            .map(|option| option.to_string())
+            .collect::<Vec<String>>()
+            .join(",")
+    )
+}
+
+// shows the lines that are different in a donor $animation and asks what to do
+fn ask_animation_conflict(term: &Term, base: &Animation, donor: &Animation) -> Result<Conflict> {
+    println!("{} is different in the donor:", style(&base.name).yellow());
+    // indentation isn't a difference
+    let lines = |anim: &Animation| {
+        anim.block
+            .iter()
+            .map(|line| line.trim().to_owned())
+            .collect::<Vec<String>>()
+    };
+    let (base_lines, donor_lines) = (lines(base), lines(donor));
+    for line in base_lines.iter().filter(|line| !donor_lines.contains(line)) {
+        println!("{}", style(format!("- {}", line)).red());
+    }
+    for line in donor_lines.iter().filter(|line| !base_lines.contains(line)) {
+        println!("{}", style(format!("+ {}", line)).green());
+    }
+    if base.smd_path() != donor.smd_path() {
+        println!(
+            "The base's uses {}, the donor's uses {}",
+            base.smd_path(),
+            donor.smd_path()
+        );
+    }
+    ask_conflict(term)
+}
+
+// asks what to do with a donor block that's different from the base's block with its name
+fn ask_conflict(term: &Term) -> Result<Conflict> {
+    loop {
+        println!(
+            "{} the donor's (default), keep the {}'s or use the {}'s for both?",
+            style("[r]ename").bold(),
+            style("[b]ase").bold(),
+            style("[d]onor").bold()
+        );
+        match term.read_line()?.trim() {
+            "" | "r" | "rename" => return Ok(Conflict::Rename),
+            "b" | "base" => return Ok(Conflict::Base),
+            "d" | "donor" => return Ok(Conflict::Donor),
+            _ => {}
+        }
+    }
+}
+
+// shows the bones whose weights are different in a donor $weightlist and asks what to do
+fn ask_weightlist_conflict(term: &Term, name: &str, diff: &[WeightDiff]) -> Result<Conflict> {
+    let mut table = Table::new();
+    table.set_header(vec!["bone", "base", "donor"]);
+    let weight = |weight: Option<f64>| weight.map_or("-".to_owned(), |weight| weight.to_string());
+    for bone in diff {
+        table.add_row(vec![
+            bone.bone.to_owned(),
+            weight(bone.weight),
+            weight(bone.other_weight),
+        ]);
+    }
+    println!(
+        "{} has different weights in the donor:\n{}",
+        style(name).yellow(),
+        table
+    );
+    ask_conflict(term)
+}
+
+// warns about donor activities that look like typos, and suggests the base's sequences
+// for activities the base doesn't use
+fn check_activities(qc: &QC, base_qc: &QC, selected: &[String]) {
+    for seq in selected.iter().map(|name| &qc.sequences[name]) {
+        if seq.activity.is_empty() {
+            continue;
+        }
+        if !activity::is_known(&seq.activity) {
+            match activity::closest(&seq.activity) {
+                Some(closest) => println!(
+                    "{}'s activity {} isn't a TF2 viewmodel activity, did you mean {}?",
+                    style(&seq.name).yellow(),
+                    seq.activity,
+                    style(closest).bold()
+                ),
+                None => println!(
+                    "{}'s activity {} isn't a TF2 viewmodel activity",
+                    style(&seq.name).yellow(),
+                    seq.activity
+                ),
+            }
+        }
+        if base_qc
+            .sequences
+            .values()
+            .any(|base_seq| base_seq.has_activity(&seq.activity))
+        {
+            continue;
+        }
+        let slots = base_qc.activity_slots(&seq.activity);
+        if !slots.is_empty() {
+            println!(
+                "The base doesn't use {}'s activity {}, its {} sequences are: {}",
+                style(&seq.name).yellow(),
+                seq.activity,
+                activity::family(&seq.activity).unwrap_or_default(),
+                slots
+                    .iter()
+                    .map(|slot| format!("{} ({})", style(&slot.name).bold(), slot.activity))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            );
+        }
+    }
+}
+
+// warns about new smds and dmxs that use bones the base skeleton doesn't have,
+// after renaming them with the bone map
+fn check_skeleton(skeleton: &[String], new_smds: &[(PathBuf, PathBuf)], bone_map: &BoneMap) {
+    if skeleton.is_empty() {
+        return;
+    }
+
+    let mut warned = false;
+    for (smd, source) in new_smds {
+        match smd::missing_bones(source, skeleton, bone_map) {
+            Ok(missing) if missing.is_empty() => {}
+            Ok(missing) => {
+                println!(
+                    "{} uses bones the base skeleton doesn't have: {}",
+                    style(smd.display()).yellow(),
+                    missing.join(", ")
+                );
+                warned = true;
+            }
+            Err(e) => println!("{} {:#}", style("Skipped the skeleton check:").yellow(), e),
+        }
+    }
+    if warned && bone_map.is_empty() {
+        println!(
+            "Use {} to rename donor bones to base bones",
+            style("--bone-map FILE").bold()
+        );
+    }
+}
+
+// tells the user which dmxs couldn't be changed, e.g. `retargeted`
+fn print_skipped_dmxs(dmxs: &[PathBuf], operation: &str) {
+    for dmx in dmxs {
+        println!(
+            "{} is a dmx, only smds can be {}, copied it as-is",
+            style(dmx.display()).yellow(),
+            operation
+        );
+    }
+}
+
+// sets the base's fps on the sequences whose smds were resampled, the ones whose smds were
+// skipped keep their fps
+fn set_conformed_fps(
+    new_qc: &mut QC,
+    qc_file: &QC,
+    conformed: &[(String, f64, f64)],
+    skipped: &[PathBuf],
+) -> Result<()> {
+    for (name, fps, base_fps) in conformed {
+        if path::contains(skipped, qc_file.sequences[name].smd_path()) {
+            println!(
+                "{} wasn't resampled, it keeps {} fps",
+                style(name).yellow(),
+                fps
+            );
+            continue;
+        }
+        new_qc.set_fps(name, *base_fps)?;
+        println!(
+            "{} is resampled from {} to {} fps",
+            style(name).green(),
+            fps,
+            base_fps
+        );
+    }
+    Ok(())
+}
+
+// tells the user which smds aren't needed by studiomdl
+fn print_orphaned(smds: &[PathBuf], reason: &str) {
+    if !smds.is_empty() {
+        println!(
+            "Orphaned smds, {}: {}",
+            reason,
+            smds.iter()
+                .map(|smd| style(smd.display()).yellow().to_string())
+                .collect::<Vec<String>>()
+                .join(", ")
+        );
+    }
+}
+
+// tells the user which old backups were removed, see --keep-backups
+fn print_pruned(backups: &[PathBuf]) {
+    if !backups.is_empty() {
+        println!(
+            "Removed old backups: {}",
+            backups
+                .iter()
+                .map(|backup| style(backup.display()).yellow().to_string())
+                .collect::<Vec<String>>()
+                .join(", ")
+        );
+    }
+}
+
+// tells the user which copied smds were moved because their path was taken by a base smd
+fn print_relocated(smds: &[PathBuf], relocated: &HashMap<PathBuf, PathBuf>) {
+    for smd in smds {
+        if let Some(original) = relocated.get(smd) {
+            println!(
+                "{} can't share its file with the base's blocks, copied to {}",
+                original.display(),
+                style(smd.display()).yellow()
+            );
+        }
+    }
+}
+
+// runs the subcommands that edit smds, writes the result to a new smd
+fn edit_smd(cmd: &Command) -> Result<()> {
+    let (smd, output) = match cmd {
+        Command::Resample {
+            smd,
+            output,
+            from_fps,
+            fps,
+            frames,
+        } => {
+            let mut parsed = Smd::new(smd)?;
+            match (fps, frames) {
+                (Some(fps), _) => parsed.conform_fps(*from_fps, *fps)?,
+                (None, Some(frames)) => parsed.resample(*frames)?,
+                (None, None) => unreachable!(),
+            }
+            (parsed, output)
+        }
+        Command::Trim {
+            smd,
+            output,
+            start,
+            end,
+        } => {
+            let mut parsed = Smd::new(smd)?;
+            let end = end.unwrap_or_else(|| parsed.frame_count().saturating_sub(1));
+            parsed.trim(*start, end)?;
+            (parsed, output)
+        }
+        Command::Reverse { smd, output } => {
+            let mut parsed = Smd::new(smd)?;
+            parsed.reverse();
+            (parsed, output)
+        }
+        Command::Concat {
+            output,
+            smds,
+            drop_first,
+        } => {
+            let mut parsed = Smd::new(&smds[0])?;
+            for smd in &smds[1..] {
+                parsed
+                    .concat(&Smd::new(smd)?, *drop_first)
+                    .with_context(|| format!("Failed to join {}", smd.display()))?;
+            }
+            (parsed, output)
+        }
+        Command::Restore { .. } => unreachable!(),
+    };
+    smd.write(output)?;
+    println!(
+        "Wrote {} with {} frames",
+        output.display(),
+        smd.frame_count()
+    );
+    Ok(())
+}
+
+// lists the backups of the output folder and restores the chosen one
+fn restore(opt: &Opt, backup: Option<&str>) -> Result<()> {
+    let backups = output::backups(&opt.output)?;
+    if backups.is_empty() {
+        println!("No backups of {} were found", opt.output.display());
+        return Ok(());
+    }
+
+    let input = match backup {
+        Some(backup) => backup.to_owned(),
+        None => {
+            for (i, backup) in backups.iter().enumerate() {
+                println!("{}: {}", i + 1, backup.display());
+            }
+            println!("Enter the number of the backup to restore, enter nothing to exit");
+            let input = Term::stdout().read_line()?;
+            if input.trim().is_empty() {
+                return Ok(());
+            }
+            input
+        }
+    };
+
+    let chosen = match input.trim().parse::<usize>() {
+        Ok(n) => backups.get(n.wrapping_sub(1)),
+        Err(_) => backups.iter().find(|backup| {
+            backup.file_name().unwrap().to_string_lossy() == input.trim()
+                || backup.as_path() == Path::new(input.trim())
+        }),
+    }
+    .with_context(|| format!("Failed to find backup: {}", input.trim()))?;
 
-    for smd in x.into_iter().zip(y) {
-        let mut smd_parent = smd.1.to_owned();
-        smd_parent.pop();
-        fs::create_dir_all(&smd_parent)
-            .with_context(|| format!("Failed to create {}", smd.1.display()))?;
-        fs::copy(&smd.0, &smd.1).with_context(|| {
-            format!("Failed to copy {} to {}", smd.0.display(), smd.1.display())
-        })?;
+    if let Some(current) = output::restore(&opt.output, chosen)? {
+        println!("Previous output was backed up to {}", current.display());
     }
+    println!("Restored {}", chosen.display());
     Ok(())
 }
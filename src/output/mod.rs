@@ -0,0 +1,696 @@
+use crate::qc::{path, QC};
+use crate::smd::retarget::BoneMap;
+use crate::smd::{self, Smd};
+use anyhow::{bail, Context, Result};
+use indexmap::IndexMap;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::fs;
+use std::fs::File;
+use std::hash::Hasher;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// a temporary sibling of the output folder that the merge is written to,
+// the output folder is only touched once everything was written successfully
+#[derive(Debug)]
+pub struct Staging {
+    // the temporary folder, e.g. `.output.tmp` for `output/`
+    dir: PathBuf,
+    // the output folder
+    output: PathBuf,
+}
+
+impl Staging {
+    // creates the temporary folder next to output, removes leftovers from a failed run
+    pub fn new<P: AsRef<Path>>(output: P) -> Result<Staging> {
+        let output = output.as_ref().to_path_buf();
+        let dir = sibling(&output, "tmp")?;
+        if dir.exists() {
+            fs::remove_dir_all(&dir)
+                .with_context(|| format!("Failed to remove {}", dir.display()))?;
+        }
+        fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+        Ok(Staging { dir, output })
+    }
+
+    // the folder everything should be written to
+    pub fn path(&self) -> &Path {
+        &self.dir
+    }
+
+    // swaps the temporary folder into place, the old output folder is kept as a backup
+    // returns the backup if there was an old output folder, there's none if it had the same
+    // files as the temporary folder, nothing is changed then
+    pub fn replace(self) -> Result<Option<PathBuf>> {
+        if self.output.exists() && same_files(&self.dir, &self.output)? {
+            return Ok(None);
+        }
+        if !self.output.exists() {
+            fs::rename(&self.dir, &self.output).with_context(|| {
+                format!(
+                    "Failed to move {} to {}",
+                    self.dir.display(),
+                    self.output.display()
+                )
+            })?;
+            return Ok(None);
+        }
+
+        let backup = backup_path(&self.output)?;
+        fs::rename(&self.output, &backup).with_context(|| {
+            format!(
+                "Failed to move {} to {}",
+                self.output.display(),
+                backup.display()
+            )
+        })?;
+        if let Err(e) = fs::rename(&self.dir, &self.output) {
+            // puts the old output back so nothing is lost
+            fs::rename(&backup, &self.output).with_context(|| {
+                format!(
+                    "Failed to restore {} from {}",
+                    self.output.display(),
+                    backup.display()
+                )
+            })?;
+            return Err(e).with_context(|| {
+                format!(
+                    "Failed to move {} to {}",
+                    self.dir.display(),
+                    self.output.display()
+                )
+            });
+        }
+        Ok(Some(backup))
+    }
+
+    // moves every written file into the output folder, files that weren't written are kept
+    // the old output folder is copied to a backup first, returns the backup if there was one
+    // there's none if the output folder already had every written file
+    pub fn merge(self) -> Result<Option<PathBuf>> {
+        let mut changed = false;
+        for file in files(&self.dir)? {
+            if !same_content(&file, self.output.join(file.strip_prefix(&self.dir)?))? {
+                changed = true;
+                break;
+            }
+        }
+        let backup = if self.output.exists() && changed {
+            let backup = backup_path(&self.output)?;
+            copy_dir(&self.output, &backup)?;
+            Some(backup)
+        } else {
+            None
+        };
+
+        fs::create_dir_all(&self.output)
+            .with_context(|| format!("Failed to create {}", self.output.display()))?;
+        for file in files(&self.dir)? {
+            let to = self.output.join(file.strip_prefix(&self.dir)?);
+            if let Some(parent) = to.parent() {
+                fs::create_dir_all(parent)
+                    .with_context(|| format!("Failed to create {}", parent.display()))?;
+            }
+            fs::rename(&file, &to).with_context(|| {
+                format!("Failed to move {} to {}", file.display(), to.display())
+            })?;
+        }
+        fs::remove_dir_all(&self.dir)
+            .with_context(|| format!("Failed to remove {}", self.dir.display()))?;
+        Ok(backup)
+    }
+}
+
+impl Drop for Staging {
+    // cleans up if the merge failed before the folder was swapped in
+    fn drop(&mut self) {
+        if self.dir.exists() {
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+}
+
+// e.g. sibling("output/", "tmp") => `.output.tmp`
+fn sibling<P: AsRef<Path>>(dir: P, suffix: &str) -> Result<PathBuf> {
+    let name = dir
+        .as_ref()
+        .file_name()
+        .with_context(|| format!("Invalid output folder: {}", dir.as_ref().display()))?;
+    let parent = match dir.as_ref().parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    Ok(parent.join(format!(".{}.{}", name.to_string_lossy(), suffix)))
+}
+
+// e.g. `output.backup-2021-07-04_13-05-59` for `output/`, the timestamp is in UTC
+fn backup_path<P: AsRef<Path>>(output: P) -> Result<PathBuf> {
+    let tmp = sibling(&output, "tmp")?;
+    let name = output.as_ref().file_name().unwrap().to_string_lossy();
+    let mut backup = tmp.with_file_name(format!("{}.backup-{}", name, timestamp()));
+    // two backups in the same second
+    let mut n = 1;
+    while backup.exists() {
+        n += 1;
+        backup = tmp.with_file_name(format!("{}.backup-{}-{}", name, timestamp(), n));
+    }
+    Ok(backup)
+}
+
+// the current UTC time as `YYYY-MM-DD_HH-MM-SS`
+pub fn timestamp() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let (days, secs) = (secs / 86400, secs % 86400);
+
+    // converts days since 1970-01-01 to a date
+    // see: http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02}_{:02}-{:02}-{:02}",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    )
+}
+
+// copies a file to a timestamped backup next to it, e.g. `base.qc.backup-2021-07-04_13-05-59`
+pub fn backup_file<P: AsRef<Path>>(file: P) -> Result<PathBuf> {
+    let backup = backup_path(&file)?;
+    fs::copy(&file, &backup).with_context(|| {
+        format!(
+            "Failed to copy {} to {}",
+            file.as_ref().display(),
+            backup.display()
+        )
+    })?;
+    Ok(backup)
+}
+
+// true if the qc file already has the lines write_qc would write
+pub fn same_qc<P: AsRef<Path>>(qc_file: P, qc_data: &[String]) -> bool {
+    match fs::read_to_string(qc_file) {
+        Ok(text) => text.lines().eq(qc_data.iter()) && text.ends_with('\n'),
+        Err(_) => false,
+    }
+}
+
+// removes the oldest backups of an output folder or file until keep are left
+// returns the removed backups
+pub fn prune_backups<P: AsRef<Path>>(original: P, keep: usize) -> Result<Vec<PathBuf>> {
+    let backups = find_backups(&original, original.as_ref().is_dir())?;
+    let old = backups.len().saturating_sub(keep);
+    for backup in &backups[..old] {
+        if backup.is_dir() {
+            fs::remove_dir_all(backup)
+        } else {
+            fs::remove_file(backup)
+        }
+        .with_context(|| format!("Failed to remove {}", backup.display()))?;
+    }
+    Ok(backups[..old].to_vec())
+}
+
+// gets the backups of an output folder, oldest first
+pub fn backups<P: AsRef<Path>>(output: P) -> Result<Vec<PathBuf>> {
+    find_backups(output, true)
+}
+
+// gets the backup folders or files of a folder or file, oldest first
+fn find_backups<P: AsRef<Path>>(original: P, dirs: bool) -> Result<Vec<PathBuf>> {
+    let tmp = sibling(&original, "tmp")?;
+    let parent = tmp.parent().unwrap();
+    let prefix = format!(
+        "{}.backup-",
+        original.as_ref().file_name().unwrap().to_string_lossy()
+    );
+    if !parent.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut backups = Vec::new();
+    for entry in
+        fs::read_dir(parent).with_context(|| format!("Failed to read {}", parent.display()))?
+    {
+        let path = entry?.path();
+        if path.is_dir() == dirs
+            && path
+                .file_name()
+                .is_some_and(|name| name.to_string_lossy().starts_with(&prefix))
+        {
+            backups.push(path);
+        }
+    }
+    backups.sort_by_cached_key(|backup| {
+        backup_order(&backup.file_name().unwrap().to_string_lossy()[prefix.len()..])
+    });
+    Ok(backups)
+}
+
+// the timestamp and number of a backup from the end of its name, e.g. `2021-07-04_13-05-59-2`,
+// the first backup in a second has no number
+fn backup_order(suffix: &str) -> (String, u32) {
+    let (timestamp, n) = match suffix.get(..19) {
+        Some(timestamp) => (timestamp, &suffix[19..]),
+        None => (suffix, ""),
+    };
+    let n = n
+        .strip_prefix('-')
+        .and_then(|n| n.parse().ok())
+        .unwrap_or(1);
+    (timestamp.to_owned(), n)
+}
+
+// replaces the output folder with a copy of a backup, the current output is backed up too
+// returns the backup of the current output if there was one
+pub fn restore<P, Q>(output: P, backup: Q) -> Result<Option<PathBuf>>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    let staging = Staging::new(&output)?;
+    copy_dir(&backup, staging.path())?;
+    staging.replace()
+}
+
+// true if both folders have the same files with the same bytes
+fn same_files<P, Q>(a: P, b: Q) -> Result<bool>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    let (a_files, b_files) = (files(&a)?, files(&b)?);
+    if a_files.len() != b_files.len() {
+        return Ok(false);
+    }
+    for file in a_files {
+        if !same_content(&file, b.as_ref().join(file.strip_prefix(&a)?))? {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+// copies a folder recursively
+fn copy_dir<P, Q>(from: P, to: Q) -> Result<()>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    for file in files(&from)? {
+        let dest = to.as_ref().join(file.strip_prefix(&from)?);
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        fs::copy(&file, &dest)
+            .with_context(|| format!("Failed to copy {} to {}", file.display(), dest.display()))?;
+    }
+    Ok(())
+}
+
+// gets the smd and dmx files in a folder that aren't in smds, paths are relative to the folder
+// smds are qc paths, they're compared ignoring case and path separators
+pub fn unreferenced_smds<P: AsRef<Path>>(dir: P, smds: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    let mut unreferenced = Vec::new();
+    for file in files(&dir)? {
+        let is_smd = file
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("smd") || ext.eq_ignore_ascii_case("dmx"));
+        let file = file.strip_prefix(&dir)?.to_path_buf();
+        if is_smd
+            && !smds.iter().any(|smd| {
+                path::normalize(smd).to_string_lossy().to_lowercase()
+                    == file.to_string_lossy().to_lowercase()
+            })
+        {
+            unreferenced.push(file);
+        }
+    }
+    unreferenced.sort();
+    Ok(unreferenced)
+}
+
+// gets every file in a folder recursively
+pub fn files<P: AsRef<Path>>(dir: P) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in
+        fs::read_dir(&dir).with_context(|| format!("Failed to read {}", dir.as_ref().display()))?
+    {
+        let path = entry?.path();
+        if path.is_dir() {
+            files.append(&mut self::files(&path)?);
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(files)
+}
+
+// writes the qc lines to a temporary file first and then moves it over qc_file
+pub fn write_qc<P: AsRef<Path>>(qc_file: P, qc_data: &[String]) -> Result<()> {
+    let tmp = sibling(&qc_file, "tmp")?;
+    let mut write = BufWriter::new(
+        File::create(&tmp).with_context(|| format!("Failed to create {}", tmp.display()))?,
+    );
+    for line in qc_data {
+        write.write_all(line.as_bytes())?;
+        write.write_all(b"\n")?;
+    }
+    write.flush()?;
+    drop(write);
+
+    fs::rename(&tmp, &qc_file).with_context(|| {
+        format!(
+            "Failed to move {} to {}",
+            tmp.display(),
+            qc_file.as_ref().display()
+        )
+    })
+}
+
+// copies smds to the base qc's folder for in-place merges, see copy_donor_smds
+// refuses to overwrite the base qc's smds and any other file in its folder with different
+// bytes, identical ones are skipped
+// returns the smds that were copied, the skipped ones were already there
+pub fn copy_smds_in_place<P, Q>(
+    smds: &[PathBuf],
+    relocated: &HashMap<PathBuf, PathBuf>,
+    qc_file: P,
+    base_qc_file: Q,
+    base_smds: &[PathBuf],
+) -> Result<Vec<PathBuf>>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    let mut base_dir = base_qc_file.as_ref().to_path_buf();
+    base_dir.pop();
+    let mut qc_dir = qc_file.as_ref().to_path_buf();
+    qc_dir.pop();
+
+    let mut new_smds = Vec::new();
+    let mut collisions = Vec::new();
+    for smd in smds {
+        let existing = path::find(&base_dir, smd);
+        if existing.is_none() && !path::contains(base_smds, smd) {
+            new_smds.push(smd.to_owned());
+        } else if !same_content(
+            path::resolve(&qc_dir, relocated.get(smd).unwrap_or(smd)),
+            existing.unwrap_or_else(|| path::resolve(&base_dir, smd)),
+        )? {
+            collisions.push(smd.display().to_string());
+        }
+    }
+    if !collisions.is_empty() {
+        bail!(
+            "Refusing to overwrite files in the base qc's folder: {}",
+            collisions.join(", ")
+        );
+    }
+
+    copy_donor_smds(&new_smds, relocated, qc_file, base_dir)?;
+    Ok(new_smds)
+}
+
+// moves the other qc's smds that would overwrite a different file in the base qc's folder
+// into a folder named after the other qc, e.g. `p_draw.smd` -> `c_scout_pistol/p_draw.smd`
+// kept tells if the base's file at an smd path has to stay, e.g. because the merged qc
+// still uses it, other files can be overwritten
+// returns the new paths mapped to the original ones
+pub fn relocate_collisions<F>(
+    qc: &mut QC,
+    base_qc: &QC,
+    kept: F,
+) -> Result<HashMap<PathBuf, PathBuf>>
+where
+    F: Fn(&Path) -> bool,
+{
+    let mut qc_dir = qc.qc_file.clone();
+    qc_dir.pop();
+    let mut base_dir = base_qc.qc_file.clone();
+    base_dir.pop();
+    let folder = qc
+        .qc_file
+        .file_stem()
+        .with_context(|| format!("Invalid qc file: {}", qc.qc_file.display()))?
+        .to_string_lossy()
+        .to_string();
+
+    let mut relocated = HashMap::new();
+    let mut smds = qc.get_smds();
+    smds.sort();
+    smds.dedup();
+    for smd in smds {
+        let base_smd = path::resolve(&base_dir, &smd);
+        let other_smd = path::resolve(&qc_dir, &smd);
+        if !kept(&smd)
+            || !base_smd.exists()
+            || !other_smd.exists()
+            || same_content(&other_smd, &base_smd)?
+        {
+            continue;
+        }
+        let from = smd.to_string_lossy().to_string();
+        let to = relocation_path(&folder, &from);
+        qc.relocate_smd(&from, &to);
+        relocated.insert(PathBuf::from(to), smd);
+    }
+    Ok(relocated)
+}
+
+// the path an smd is moved to so it gets its own file, inside folder
+// e.g. `c_scout_pistol\anims\draw.smd` for `anims\draw.smd`, keeps the qc's path separator
+pub fn relocation_path(folder: &str, smd: &str) -> String {
+    if smd.contains('\\') {
+        format!("{}\\{}", folder, smd)
+    } else {
+        format!("{}/{}", folder, smd)
+    }
+}
+
+// true if both files exist and have the same bytes
+pub fn same_content<P, Q>(a: P, b: Q) -> Result<bool>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    if !a.as_ref().exists() || !b.as_ref().exists() {
+        return Ok(false);
+    }
+    if fs::metadata(&a)?.len() != fs::metadata(&b)?.len() {
+        return Ok(false);
+    }
+    let read = |p: &Path| fs::read(p).with_context(|| format!("Failed to read {}", p.display()));
+    Ok(read(a.as_ref())? == read(b.as_ref())?)
+}
+
+// gets the file each smd is copied from, relocated smds are copied from their original path
+pub fn smd_sources<P: AsRef<Path>>(
+    smds: &[PathBuf],
+    relocated: &HashMap<PathBuf, PathBuf>,
+    qc_file: P,
+) -> Vec<(PathBuf, PathBuf)> {
+    let mut qc_path_parent = qc_file.as_ref().to_path_buf();
+    qc_path_parent.pop();
+    smds.iter()
+        .map(|smd| {
+            (
+                smd.to_owned(),
+                path::resolve(&qc_path_parent, relocated.get(smd).unwrap_or(smd)),
+            )
+        })
+        .collect()
+}
+
+// finds smds with the same bytes as an earlier smd, `smds` is (smd, the file it's copied from)
+// returns the duplicates mapped to the smd they're identical to, in the order of smds
+pub fn find_duplicates(smds: &[(PathBuf, PathBuf)]) -> Result<IndexMap<PathBuf, PathBuf>> {
+    let mut duplicates = IndexMap::new();
+    // (size, hash) -> smds with that size and hash
+    let mut seen: HashMap<(u64, u64), Vec<&(PathBuf, PathBuf)>> = HashMap::new();
+    let mut visited = Vec::new();
+    for smd in smds {
+        // skips smds used by more than one block and files that don't exist
+        if visited.contains(&&smd.0) || !smd.1.exists() {
+            continue;
+        }
+        visited.push(&smd.0);
+
+        let key = (fs::metadata(&smd.1)?.len(), hash_file(&smd.1)?);
+        let candidates = seen.entry(key).or_default();
+        let mut original = None;
+        for candidate in candidates.iter() {
+            if same_content(&candidate.1, &smd.1)? {
+                original = Some(candidate.0.to_owned());
+                break;
+            }
+        }
+        match original {
+            Some(original) => {
+                duplicates.insert(smd.0.to_owned(), original);
+            }
+            None => candidates.push(smd),
+        }
+    }
+    Ok(duplicates)
+}
+
+// hashes the bytes of a file
+pub fn hash_file<P: AsRef<Path>>(file: P) -> Result<u64> {
+    let mut hasher = DefaultHasher::new();
+    hasher.write(
+        &fs::read(&file).with_context(|| format!("Failed to read {}", file.as_ref().display()))?,
+    );
+    Ok(hasher.finish())
+}
+
+// copies the other qc's smds, relocated smds are copied from their original path
+pub fn copy_donor_smds<P, Q>(
+    smds: &[PathBuf],
+    relocated: &HashMap<PathBuf, PathBuf>,
+    qc_file: P,
+    output_dir: Q,
+) -> Result<()>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    copy_files(
+        &smd_sources(smds, relocated, qc_file)
+            .into_iter()
+            .map(|(smd, source)| {
+                let to = output_dir.as_ref().join(path::output_path(&smd, &source));
+                (source, to)
+            })
+            .collect::<Vec<(PathBuf, PathBuf)>>(),
+    )
+}
+
+// retargets the copies copy_donor_smds made in output_dir to the base skeleton,
+// see Smd::retarget, returns the dmxs that were skipped because only smds can be rewritten
+pub fn retarget_smds<P, Q>(
+    smds: &[PathBuf],
+    relocated: &HashMap<PathBuf, PathBuf>,
+    qc_file: P,
+    output_dir: Q,
+    map: &BoneMap,
+    skeleton: &[String],
+) -> Result<Vec<PathBuf>>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    rewrite_copies(smds, relocated, qc_file, output_dir, |smd, parsed| {
+        parsed
+            .retarget(map, skeleton)
+            .with_context(|| format!("Failed to retarget: {}", smd.display()))
+    })
+}
+
+// resamples the copies copy_donor_smds made in output_dir, `fps` maps the smds to
+// their fps and the fps they should have, see Smd::conform_fps
+// returns the dmxs that were skipped because only smds can be rewritten
+pub fn resample_smds<P, Q>(
+    fps: &HashMap<PathBuf, (f64, f64)>,
+    relocated: &HashMap<PathBuf, PathBuf>,
+    qc_file: P,
+    output_dir: Q,
+) -> Result<Vec<PathBuf>>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    let mut smds = fps.keys().cloned().collect::<Vec<PathBuf>>();
+    smds.sort();
+    rewrite_copies(&smds, relocated, qc_file, output_dir, |smd, parsed| {
+        let (fps, new_fps) = fps[smd];
+        parsed
+            .conform_fps(fps, new_fps)
+            .with_context(|| format!("Failed to resample: {}", smd.display()))
+    })
+}
+
+// parses, changes and writes back the copies copy_donor_smds made in output_dir
+// returns the dmxs that were skipped
+fn rewrite_copies<P, Q, F>(
+    smds: &[PathBuf],
+    relocated: &HashMap<PathBuf, PathBuf>,
+    qc_file: P,
+    output_dir: Q,
+    mut rewrite: F,
+) -> Result<Vec<PathBuf>>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+    F: FnMut(&PathBuf, &mut Smd) -> Result<()>,
+{
+    let mut rewritten = Vec::new();
+    let mut skipped = Vec::new();
+    for (smd, source) in smd_sources(smds, relocated, qc_file) {
+        let copy = output_dir.as_ref().join(path::output_path(&smd, &source));
+        // the same smd can be used by more than one block
+        if rewritten.contains(&copy) {
+            continue;
+        }
+        if smd::is_dmx(&copy) {
+            skipped.push(smd);
+            continue;
+        }
+        let mut parsed = Smd::new(&copy)?;
+        rewrite(&smd, &mut parsed)?;
+        parsed.write(&copy)?;
+        rewritten.push(copy);
+    }
+    Ok(skipped)
+}
+
+// copies smds relative to the qc file's folder to the same relative path in output_dir
+pub fn copy_smds<P, Q>(smds: &[PathBuf], qc_file: P, output_dir: Q) -> Result<()>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    copy_donor_smds(smds, &HashMap::new(), qc_file, output_dir)
+}
+
+// copies each file to its destination, creates the destination's folders
+// destinations that already have the same bytes are skipped
+fn copy_files(files: &[(PathBuf, PathBuf)]) -> Result<()> {
+    let mut copied = Vec::new();
+    for smd in files {
+        // the same smd can be used by more than one block
+        if copied.contains(&&smd.1) {
+            continue;
+        }
+        copied.push(&smd.1);
+        if same_content(&smd.0, &smd.1)? {
+            continue;
+        }
+
+        let mut smd_parent = smd.1.to_owned();
+        smd_parent.pop();
+        fs::create_dir_all(&smd_parent)
+            .with_context(|| format!("Failed to create {}", smd.1.display()))?;
+        fs::copy(&smd.0, &smd.1).with_context(|| {
+            format!("Failed to copy {} to {}", smd.0.display(), smd.1.display())
+        })?;
+    }
+    Ok(())
+}
@@ -0,0 +1,400 @@
+use crate::qc::sequence::Sequence;
+use crate::qc::QC;
+
+// the viewmodel activities of tf2, from ai_activity.h and tf's additions to it
+pub const ACTIVITIES: [&str; 252] = [
+    // generic viewmodel activities from hl2
+    "ACT_VM_DRAW",
+    "ACT_VM_HOLSTER",
+    "ACT_VM_IDLE",
+    "ACT_VM_FIDGET",
+    "ACT_VM_PULLBACK",
+    "ACT_VM_PULLBACK_HIGH",
+    "ACT_VM_PULLBACK_LOW",
+    "ACT_VM_THROW",
+    "ACT_VM_PULLPIN",
+    "ACT_VM_PRIMARYATTACK",
+    "ACT_VM_SECONDARYATTACK",
+    "ACT_VM_RELOAD",
+    "ACT_VM_DRYFIRE",
+    "ACT_VM_HITLEFT",
+    "ACT_VM_HITLEFT2",
+    "ACT_VM_HITRIGHT",
+    "ACT_VM_HITRIGHT2",
+    "ACT_VM_HITCENTER",
+    "ACT_VM_HITCENTER2",
+    "ACT_VM_MISSLEFT",
+    "ACT_VM_MISSLEFT2",
+    "ACT_VM_MISSRIGHT",
+    "ACT_VM_MISSRIGHT2",
+    "ACT_VM_MISSCENTER",
+    "ACT_VM_MISSCENTER2",
+    "ACT_VM_HAULBACK",
+    "ACT_VM_SWINGHARD",
+    "ACT_VM_SWINGMISS",
+    "ACT_VM_SWINGHIT",
+    "ACT_VM_IDLE_TO_LOWERED",
+    "ACT_VM_IDLE_LOWERED",
+    "ACT_VM_LOWERED_TO_IDLE",
+    "ACT_VM_RECOIL1",
+    "ACT_VM_RECOIL2",
+    "ACT_VM_RECOIL3",
+    "ACT_VM_PICKUP",
+    "ACT_VM_RELEASE",
+    // primary
+    "ACT_PRIMARY_VM_DRAW",
+    "ACT_PRIMARY_VM_HOLSTER",
+    "ACT_PRIMARY_VM_IDLE",
+    "ACT_PRIMARY_VM_PULLBACK",
+    "ACT_PRIMARY_VM_PRIMARYATTACK",
+    "ACT_PRIMARY_VM_SECONDARYATTACK",
+    "ACT_PRIMARY_VM_RELOAD",
+    "ACT_PRIMARY_VM_DRYFIRE",
+    "ACT_PRIMARY_VM_IDLE_TO_LOWERED",
+    "ACT_PRIMARY_VM_IDLE_LOWERED",
+    "ACT_PRIMARY_VM_LOWERED_TO_IDLE",
+    "ACT_PRIMARY_RELOAD_START",
+    "ACT_PRIMARY_RELOAD_FINISH",
+    "ACT_PRIMARY_VM_RELOAD_2",
+    "ACT_PRIMARY_RELOAD_START_2",
+    "ACT_PRIMARY_RELOAD_FINISH_2",
+    "ACT_PRIMARY_VM_RELOAD_3",
+    "ACT_PRIMARY_RELOAD_START_3",
+    "ACT_PRIMARY_RELOAD_FINISH_3",
+    "ACT_PRIMARY_VM_PRIMARYATTACK_3",
+    "ACT_PRIMARY_VM_INSPECT_START",
+    "ACT_PRIMARY_VM_INSPECT_IDLE",
+    "ACT_PRIMARY_VM_INSPECT_END",
+    // secondary
+    "ACT_SECONDARY_VM_DRAW",
+    "ACT_SECONDARY_VM_HOLSTER",
+    "ACT_SECONDARY_VM_IDLE",
+    "ACT_SECONDARY_VM_PULLBACK",
+    "ACT_SECONDARY_VM_PRIMARYATTACK",
+    "ACT_SECONDARY_VM_SECONDARYATTACK",
+    "ACT_SECONDARY_VM_RELOAD",
+    "ACT_SECONDARY_VM_DRYFIRE",
+    "ACT_SECONDARY_VM_IDLE_TO_LOWERED",
+    "ACT_SECONDARY_VM_IDLE_LOWERED",
+    "ACT_SECONDARY_VM_LOWERED_TO_IDLE",
+    "ACT_SECONDARY_RELOAD_START",
+    "ACT_SECONDARY_RELOAD_FINISH",
+    "ACT_SECONDARY_VM_ALTATTACK",
+    "ACT_SECONDARY_VM_DRAW_2",
+    "ACT_SECONDARY_VM_IDLE_2",
+    "ACT_SECONDARY_VM_PRIMARYATTACK_2",
+    "ACT_SECONDARY_VM_RELOAD_2",
+    "ACT_SECONDARY_VM_RELOAD2",
+    "ACT_SECONDARY_VM_INSPECT_START",
+    "ACT_SECONDARY_VM_INSPECT_IDLE",
+    "ACT_SECONDARY_VM_INSPECT_END",
+    // melee
+    "ACT_MELEE_VM_DRAW",
+    "ACT_MELEE_VM_HOLSTER",
+    "ACT_MELEE_VM_IDLE",
+    "ACT_MELEE_VM_PULLBACK",
+    "ACT_MELEE_VM_PRIMARYATTACK",
+    "ACT_MELEE_VM_SECONDARYATTACK",
+    "ACT_MELEE_VM_RELOAD",
+    "ACT_MELEE_VM_DRYFIRE",
+    "ACT_MELEE_VM_IDLE_TO_LOWERED",
+    "ACT_MELEE_VM_IDLE_LOWERED",
+    "ACT_MELEE_VM_LOWERED_TO_IDLE",
+    "ACT_MELEE_VM_STUN",
+    "ACT_MELEE_VM_HITCENTER",
+    "ACT_MELEE_VM_SWINGHARD",
+    "ACT_MELEE_VM_THROW",
+    "ACT_MELEE_VM_INSPECT_START",
+    "ACT_MELEE_VM_INSPECT_IDLE",
+    "ACT_MELEE_VM_INSPECT_END",
+    // all-class melee weapons
+    "ACT_MELEE_ALLCLASS_VM_DRAW",
+    "ACT_MELEE_ALLCLASS_VM_HOLSTER",
+    "ACT_MELEE_ALLCLASS_VM_IDLE",
+    "ACT_MELEE_ALLCLASS_VM_PULLBACK",
+    "ACT_MELEE_ALLCLASS_VM_PRIMARYATTACK",
+    "ACT_MELEE_ALLCLASS_VM_SECONDARYATTACK",
+    "ACT_MELEE_ALLCLASS_VM_RELOAD",
+    "ACT_MELEE_ALLCLASS_VM_DRYFIRE",
+    "ACT_MELEE_ALLCLASS_VM_IDLE_TO_LOWERED",
+    "ACT_MELEE_ALLCLASS_VM_IDLE_LOWERED",
+    "ACT_MELEE_ALLCLASS_VM_LOWERED_TO_IDLE",
+    "ACT_MELEE_ALLCLASS_VM_STUN",
+    "ACT_MELEE_ALLCLASS_VM_HITCENTER",
+    "ACT_MELEE_ALLCLASS_VM_SWINGHARD",
+    "ACT_MELEE_ALLCLASS_VM_INSPECT_START",
+    "ACT_MELEE_ALLCLASS_VM_INSPECT_IDLE",
+    "ACT_MELEE_ALLCLASS_VM_INSPECT_END",
+    // the inspects of weapons that use another slot's animations
+    "ACT_PRIMARY_ALT1_VM_INSPECT_START",
+    "ACT_PRIMARY_ALT1_VM_INSPECT_IDLE",
+    "ACT_PRIMARY_ALT1_VM_INSPECT_END",
+    "ACT_SECONDARY_ALT1_VM_INSPECT_START",
+    "ACT_SECONDARY_ALT1_VM_INSPECT_IDLE",
+    "ACT_SECONDARY_ALT1_VM_INSPECT_END",
+    "ACT_MELEE_ALT1_VM_INSPECT_START",
+    "ACT_MELEE_ALT1_VM_INSPECT_IDLE",
+    "ACT_MELEE_ALT1_VM_INSPECT_END",
+    "ACT_PRIMARY_ALT2_VM_INSPECT_START",
+    "ACT_PRIMARY_ALT2_VM_INSPECT_IDLE",
+    "ACT_PRIMARY_ALT2_VM_INSPECT_END",
+    "ACT_SECONDARY_ALT2_VM_INSPECT_START",
+    "ACT_SECONDARY_ALT2_VM_INSPECT_IDLE",
+    "ACT_SECONDARY_ALT2_VM_INSPECT_END",
+    "ACT_MELEE_ALT2_VM_INSPECT_START",
+    "ACT_MELEE_ALT2_VM_INSPECT_IDLE",
+    "ACT_MELEE_ALT2_VM_INSPECT_END",
+    "ACT_PRIMARY_ALT3_VM_INSPECT_START",
+    "ACT_PRIMARY_ALT3_VM_INSPECT_IDLE",
+    "ACT_PRIMARY_ALT3_VM_INSPECT_END",
+    "ACT_SECONDARY_ALT3_VM_INSPECT_START",
+    "ACT_SECONDARY_ALT3_VM_INSPECT_IDLE",
+    "ACT_SECONDARY_ALT3_VM_INSPECT_END",
+    "ACT_MELEE_ALT3_VM_INSPECT_START",
+    "ACT_MELEE_ALT3_VM_INSPECT_IDLE",
+    "ACT_MELEE_ALT3_VM_INSPECT_END",
+    // pda
+    "ACT_PDA_VM_DRAW",
+    "ACT_PDA_VM_HOLSTER",
+    "ACT_PDA_VM_IDLE",
+    "ACT_PDA_VM_PULLBACK",
+    "ACT_PDA_VM_PRIMARYATTACK",
+    "ACT_PDA_VM_SECONDARYATTACK",
+    "ACT_PDA_VM_RELOAD",
+    "ACT_PDA_VM_DRYFIRE",
+    "ACT_PDA_VM_IDLE_TO_LOWERED",
+    "ACT_PDA_VM_IDLE_LOWERED",
+    "ACT_PDA_VM_LOWERED_TO_IDLE",
+    "ACT_ENGINEER_PDA1_VM_DRAW",
+    "ACT_ENGINEER_PDA2_VM_DRAW",
+    "ACT_ENGINEER_BLD_VM_DRAW",
+    "ACT_ENGINEER_PDA1_VM_IDLE",
+    "ACT_ENGINEER_PDA2_VM_IDLE",
+    "ACT_ENGINEER_BLD_VM_IDLE",
+    // items, e.g. lunchboxes and banners
+    "ACT_ITEM1_VM_DRAW",
+    "ACT_ITEM1_VM_HOLSTER",
+    "ACT_ITEM1_VM_IDLE",
+    "ACT_ITEM1_VM_PULLBACK",
+    "ACT_ITEM1_VM_PRIMARYATTACK",
+    "ACT_ITEM1_VM_SECONDARYATTACK",
+    "ACT_ITEM1_VM_RELOAD",
+    "ACT_ITEM1_VM_DRYFIRE",
+    "ACT_ITEM1_VM_IDLE_TO_LOWERED",
+    "ACT_ITEM1_VM_IDLE_LOWERED",
+    "ACT_ITEM1_VM_LOWERED_TO_IDLE",
+    "ACT_ITEM1_VM_INSPECT_START",
+    "ACT_ITEM1_VM_INSPECT_IDLE",
+    "ACT_ITEM1_VM_INSPECT_END",
+    "ACT_ITEM2_VM_DRAW",
+    "ACT_ITEM2_VM_HOLSTER",
+    "ACT_ITEM2_VM_IDLE",
+    "ACT_ITEM2_VM_PULLBACK",
+    "ACT_ITEM2_VM_PRIMARYATTACK",
+    "ACT_ITEM2_VM_SECONDARYATTACK",
+    "ACT_ITEM2_VM_RELOAD",
+    "ACT_ITEM2_VM_DRYFIRE",
+    "ACT_ITEM2_VM_IDLE_TO_LOWERED",
+    "ACT_ITEM2_VM_IDLE_LOWERED",
+    "ACT_ITEM2_VM_LOWERED_TO_IDLE",
+    "ACT_ITEM2_VM_INSPECT_START",
+    "ACT_ITEM2_VM_INSPECT_IDLE",
+    "ACT_ITEM2_VM_INSPECT_END",
+    "ACT_ITEM3_VM_DRAW",
+    "ACT_ITEM3_VM_HOLSTER",
+    "ACT_ITEM3_VM_IDLE",
+    "ACT_ITEM3_VM_PULLBACK",
+    "ACT_ITEM3_VM_PRIMARYATTACK",
+    "ACT_ITEM3_VM_SECONDARYATTACK",
+    "ACT_ITEM3_VM_RELOAD",
+    "ACT_ITEM3_VM_DRYFIRE",
+    "ACT_ITEM3_VM_IDLE_TO_LOWERED",
+    "ACT_ITEM3_VM_IDLE_LOWERED",
+    "ACT_ITEM3_VM_LOWERED_TO_IDLE",
+    "ACT_ITEM3_VM_THROW",
+    "ACT_ITEM4_VM_DRAW",
+    "ACT_ITEM4_VM_HOLSTER",
+    "ACT_ITEM4_VM_IDLE",
+    "ACT_ITEM4_VM_PULLBACK",
+    "ACT_ITEM4_VM_PRIMARYATTACK",
+    "ACT_ITEM4_VM_SECONDARYATTACK",
+    "ACT_ITEM4_VM_RELOAD",
+    "ACT_ITEM4_VM_DRYFIRE",
+    "ACT_ITEM4_VM_IDLE_TO_LOWERED",
+    "ACT_ITEM4_VM_IDLE_LOWERED",
+    "ACT_ITEM4_VM_LOWERED_TO_IDLE",
+    // weapons with a special state, e.g. the knife's backstab
+    "ACT_VM_DRAW_SPECIAL",
+    "ACT_VM_HOLSTER_SPECIAL",
+    "ACT_VM_IDLE_SPECIAL",
+    "ACT_VM_PULLBACK_SPECIAL",
+    "ACT_VM_PRIMARYATTACK_SPECIAL",
+    "ACT_VM_SECONDARYATTACK_SPECIAL",
+    "ACT_VM_HITCENTER_SPECIAL",
+    "ACT_VM_SWINGHARD_SPECIAL",
+    "ACT_VM_IDLE_TO_LOWERED_SPECIAL",
+    "ACT_VM_IDLE_LOWERED_SPECIAL",
+    "ACT_VM_LOWERED_TO_IDLE_SPECIAL",
+    "ACT_BACKSTAB_VM_DOWN",
+    "ACT_BACKSTAB_VM_UP",
+    "ACT_BACKSTAB_VM_IDLE",
+    // passtime
+    "ACT_BALL_VM_CATCH",
+    "ACT_BALL_VM_PICKUP",
+    "ACT_BALL_VM_IDLE",
+    "ACT_BALL_VM_THROW_START",
+    "ACT_BALL_VM_THROW_LOOP",
+    "ACT_BALL_VM_THROW_END",
+    // grappling hook
+    "ACT_GRAPPLE_DRAW",
+    "ACT_GRAPPLE_IDLE",
+    "ACT_GRAPPLE_FIRE_START",
+    "ACT_GRAPPLE_FIRE_IDLE",
+    "ACT_GRAPPLE_PULL_START",
+    "ACT_GRAPPLE_PULL_IDLE",
+    "ACT_GRAPPLE_PULL_END",
+    // halloween spells and throwables
+    "ACT_SPELL_VM_DRAW",
+    "ACT_SPELL_VM_IDLE",
+    "ACT_SPELL_VM_ARM",
+    "ACT_SPELL_VM_FIRE",
+    "ACT_THROWABLE_VM_DRAW",
+    "ACT_THROWABLE_VM_IDLE",
+    "ACT_THROWABLE_VM_FIRE",
+    // bread monster
+    "ACT_BREADMONSTER_VM_DRAW",
+    "ACT_BREADMONSTER_VM_IDLE",
+    "ACT_BREADMONSTER_VM_PRIMARYATTACK",
+    "ACT_BREADMONSTER_VM_INSPECT_START",
+    "ACT_BREADMONSTER_VM_INSPECT_IDLE",
+    "ACT_BREADMONSTER_VM_INSPECT_END",
+];
+
+// the families activities are grouped in, an activity is in the first family with one of
+// the words between the `_`s of its name, numbers at the end of words are ignored,
+// e.g. `RECOIL2`
+const FAMILIES: [(&str, &[&str]); 7] = [
+    ("inspect", &["INSPECT"]),
+    ("reload", &["RELOAD"]),
+    ("lowered", &["LOWERED"]),
+    ("draw", &["DRAW", "PICKUP"]),
+    ("holster", &["HOLSTER"]),
+    (
+        "attack",
+        &[
+            "ATTACK",
+            "PRIMARYATTACK",
+            "SECONDARYATTACK",
+            "ALTATTACK",
+            "FIRE",
+            "DRYFIRE",
+            "HIT",
+            "HITCENTER",
+            "HITLEFT",
+            "HITRIGHT",
+            "MISS",
+            "MISSCENTER",
+            "MISSLEFT",
+            "MISSRIGHT",
+            "SWING",
+            "SWINGHARD",
+            "SWINGHIT",
+            "SWINGMISS",
+            "THROW",
+            "PULL",
+            "PULLBACK",
+            "PULLPIN",
+            "HAULBACK",
+            "STUN",
+            "BACKSTAB",
+            "RECOIL",
+            "RELEASE",
+            "ARM",
+            "CATCH",
+        ],
+    ),
+    ("idle", &["IDLE", "FIDGET"]),
+];
+
+// an activity that ends with one of these is an idle even with an attack word before it,
+// e.g. `ACT_GRAPPLE_FIRE_IDLE` and `ACT_BACKSTAB_VM_IDLE`
+const IDLE_SUFFIXES: [&str; 2] = ["IDLE", "FIDGET"];
+
+// true if the activity is in the catalogue, activities are compared ignoring case like
+// studiomdl does
+pub fn is_known<T: AsRef<str>>(activity: T) -> bool {
+    ACTIVITIES
+        .iter()
+        .any(|known| known.eq_ignore_ascii_case(activity.as_ref()))
+}
+
+// the activity in the catalogue an unknown activity is most likely a typo of,
+// None if it's known or nothing is close
+pub fn closest<T: AsRef<str>>(activity: T) -> Option<&'static str> {
+    let activity = activity.as_ref().to_uppercase();
+    if is_known(&activity) {
+        return None;
+    }
+    ACTIVITIES
+        .iter()
+        .map(|known| (distance(known, &activity), *known))
+        .filter(|(distance, _)| *distance <= 3)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, known)| known)
+}
+
+// the family of an activity, e.g. `reload` for `ACT_PRIMARY_RELOAD_START`
+pub fn family<T: AsRef<str>>(activity: T) -> Option<&'static str> {
+    let activity = activity.as_ref().to_uppercase();
+    let words = activity
+        .split('_')
+        .map(|word| word.trim_end_matches(|c: char| c.is_ascii_digit()))
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<&str>>();
+    let idle = words
+        .last()
+        .is_some_and(|word| IDLE_SUFFIXES.contains(word));
+    FAMILIES
+        .iter()
+        .filter(|(family, _)| !(idle && *family == "attack"))
+        .find(|(_, family_words)| words.iter().any(|word| family_words.contains(word)))
+        .map(|(family, _)| *family)
+}
+
+// the names of the families
+pub fn families() -> Vec<&'static str> {
+    FAMILIES.iter().map(|(family, _)| *family).collect()
+}
+
+// the number of single-character edits between two strings
+fn distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<char>>();
+    let mut previous = (0..=b.len()).collect::<Vec<usize>>();
+    for (i, a) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b) in b.iter().enumerate() {
+            let substitution = previous[j] + if a == *b { 0 } else { 1 };
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+impl QC {
+    // the sequences with an activity in the same family as activity, sorted by name,
+    // e.g. the base's draw sequences for a donor's draw that uses another slot's activity
+    pub fn activity_slots<T: AsRef<str>>(&self, activity: T) -> Vec<&Sequence> {
+        let family = match family(activity) {
+            Some(family) => family,
+            None => return Vec::new(),
+        };
+        let mut sequences = self
+            .sequences
+            .values()
+            .filter(|seq| self::family(&seq.activity) == Some(family))
+            .collect::<Vec<&Sequence>>();
+        sequences.sort_by(|a, b| a.name.cmp(&b.name));
+        sequences
+    }
+}
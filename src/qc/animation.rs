@@ -1,4 +1,5 @@
-use crate::qc::QC;
+use crate::qc::document::BlockId;
+use crate::qc::{path, relocate_block, rename_header, QCCommand, QC};
 use crate::{dequote, dequote_next, dequote_nth};
 use anyhow::{bail, Result};
 
@@ -13,20 +14,26 @@ pub struct Animation {
     pub block: Vec<String>,
     // the $weightlist it uses
     pub weightlist: String,
+    // the $pushd folder the block is in, relative to the qc file's folder, empty if none
+    pub dir: String,
     // the index in the block where the start the $animation is found, add one to get line number
     pub start: usize,
     // the index in the block where the `}` is found, add one to get the line number
     pub end: usize,
+    // the block's lines in the qc's document
+    pub id: BlockId,
 }
 
 impl Animation {
-    pub fn parse(block: &Vec<String>, idx: usize) -> Result<Animation> {
-        let mut animation: Animation = Default::default();
-
-        animation.start = idx;
-        animation.end = idx + block.len() - 1;
+    pub fn parse(block: &[String], idx: usize) -> Result<Animation> {
+        let mut animation = Animation {
+            start: idx,
+            end: idx + block.len() - 1,
+            ..Default::default()
+        };
 
         for line in block {
+            let line = line.trim();
             let mut line_split = line.split_whitespace();
             // gets $animation name and smd path if it's in sca format
             if line.starts_with("$animation") {
@@ -46,63 +53,165 @@ impl Animation {
                 }
             } else if line.starts_with("weightlist") {
                 animation.weightlist = dequote_nth!(line_split, 1);
-            } else if line.contains(".smd") {
+            } else if path::is_animation_file(line) {
                 animation.smd = dequote!(line);
             }
         }
-        animation.block = block.clone();
+        animation.block = block.to_vec();
         Ok(animation)
     }
+
+    // the smd path relative to the qc file's folder, includes the $pushd folder
+    pub fn smd_path(&self) -> String {
+        if self.dir.is_empty() {
+            self.smd.to_owned()
+        } else {
+            format!("{}/{}", self.dir, self.smd)
+        }
+    }
+
+    // true if both blocks are the same and use the same smd, colliding smds with different
+    // bytes were already moved by relocate_collisions
+    // the headers are compared with the same name, a single-line block has its options there
+    // indentation isn't compared
+    pub fn same_as(&self, other: &Animation) -> bool {
+        self.smd_path() == other.smd_path()
+            && rename_header(self.block[0].trim(), &self.name)
+                == rename_header(other.block[0].trim(), &self.name)
+            && self.block[1..]
+                .iter()
+                .map(|line| line.trim())
+                .eq(other.block[1..].iter().map(|line| line.trim()))
+    }
 }
 
 impl QC {
+    // appends other's animation where the placement wants it, by default before the first
+    // $animation, after the last $weightlist or before the first $sequence, and updates self
     pub fn append_animation<T: AsRef<str>>(
         &mut self,
         other_qc: &Self,
         anim: T,
-        qc_data: &mut Vec<String>,
     ) -> Result<Animation> {
-        let animation_rev = other_qc.animations[anim.as_ref()].block.iter().rev();
-        if !self.animations.is_empty() {
-            animation_rev.for_each(|line| {
-                qc_data.insert(
-                    self.animations
-                        .iter()
-                        .min_by(|(_, x), (_, y)| x.start.cmp(&y.start))
-                        .unwrap()
-                        .1
-                        .start,
-                    line.to_owned(),
-                )
-            });
-        } else if !self.weightlists.is_empty() {
-            animation_rev.for_each(|line| {
-                qc_data.insert(
-                    self.weightlists
-                        .iter()
-                        .max_by(|(_, x), (_, y)| x.end.cmp(&y.end))
-                        .unwrap()
-                        .1
-                        .end
-                        + 1,
-                    line.to_owned(),
-                )
-            });
-        } else if !self.sequences.is_empty() {
-            animation_rev.for_each(|line| {
-                qc_data.insert(
-                    self.sequences
-                        .iter()
-                        .min_by(|(_, x), (_, y)| x.start.cmp(&y.start))
-                        .unwrap()
-                        .1
-                        .start,
-                    line.to_owned(),
-                )
-            });
+        let mut animation = match other_qc.animations.get(anim.as_ref()) {
+            Some(animation) => animation.clone(),
+            None => bail!(
+                "[$animation Error] `{}` isn't in {}",
+                anim.as_ref(),
+                other_qc.qc_file.display()
+            ),
+        };
+        let block = animation.block.clone();
+        if let Some((id, dir)) =
+            self.place_block(QCCommand::Animation, block.clone(), &other_qc.qc_file)?
+        {
+            animation.id = id;
+            animation.dir = dir;
+            self.animations.insert(animation.name.to_owned(), animation);
+        } else if let Some((_, first)) = self.animations.first() {
+            animation.id = self.document.insert_before(first.id, block);
+            animation.dir = first.dir.to_owned();
+            self.animations
+                .shift_insert(0, animation.name.to_owned(), animation);
+        } else if let Some((_, last)) = self.weightlists.last() {
+            animation.id = self.document.insert_after(last.id, block);
+            animation.dir = last.dir.to_owned();
+            self.animations.insert(animation.name.to_owned(), animation);
+        } else if let Some((_, first)) = self.sequences.first() {
+            animation.id = self.document.insert_before(first.id, block);
+            animation.dir = first.dir.to_owned();
+            self.animations.insert(animation.name.to_owned(), animation);
+        } else {
+            bail!(
+                "[$animation Error] {} has no block to put `{}` next to",
+                self.qc_file.display(),
+                animation.name
+            );
         }
 
-        *self = Self::parse(&self.qc_file.clone(), qc_data.clone().into_iter())?;
+        let animation = &self.animations[anim.as_ref()];
+        let (id, start, end) = (animation.id, animation.start, animation.end);
+        self.annotate(id, "appended", &other_qc.qc_file, start, end);
+
+        // studiomdl uses the $animation for the $sequences with its name
+        for seq in self.sequences.values_mut() {
+            if seq.smd == anim.as_ref() {
+                seq.uses_animation = true;
+            }
+        }
+        Ok(other_qc.animations[anim.as_ref()].clone())
+    }
+
+    // the $animations used by the sequences, sorted
+    pub fn used_animations(&self, names: &[String]) -> Vec<String> {
+        let mut animations = names
+            .iter()
+            .filter_map(|name| self.sequences.get(name))
+            .filter(|seq| seq.uses_animation && self.animations.contains_key(&seq.smd))
+            .map(|seq| seq.smd.to_owned())
+            .collect::<Vec<String>>();
+        animations.sort();
+        animations.dedup();
+        animations
+    }
+
+    // renames an $animation and the references to it in the $sequences, without changing
+    // the qc file, e.g. so a donor's animation can be appended next to a different base
+    // animation with the same name
+    pub fn rename_animation<T: AsRef<str>>(&mut self, anim: T, new_name: T) -> Result<()> {
+        let (anim, new_name) = (anim.as_ref(), new_name.as_ref());
+        let (index, _, mut animation) = match self.animations.shift_remove_full(anim) {
+            Some(animation) => animation,
+            None => bail!(
+                "[$animation Error] `{}` isn't in {}",
+                anim,
+                self.qc_file.display()
+            ),
+        };
+        animation.block[0] = rename_header(&animation.block[0], new_name);
+        animation.name = new_name.to_owned();
+        self.animations
+            .shift_insert(index, new_name.to_owned(), animation);
+
+        for seq in self.sequences.values_mut() {
+            if seq.uses_animation && seq.smd == anim {
+                // studiomdl doesn't allow a $sequence with an $animation's name
+                relocate_block(&mut seq.block, anim, new_name);
+                seq.smd = new_name.to_owned();
+                seq.parse_options();
+            }
+        }
+        Ok(())
+    }
+
+    // replaces self's (base qc) $animation with other_qc's one with the same name
+    pub fn replace_animation<T: AsRef<str>>(
+        &mut self,
+        other_qc: &Self,
+        anim: T,
+    ) -> Result<Animation> {
+        let (base_anim, mut animation) = match (
+            self.animations.get(anim.as_ref()),
+            other_qc.animations.get(anim.as_ref()),
+        ) {
+            (Some(base_anim), Some(animation)) => (base_anim, animation.clone()),
+            (None, _) => bail!(
+                "[$animation Error] `{}` isn't in {}",
+                anim.as_ref(),
+                self.qc_file.display()
+            ),
+            (_, None) => bail!(
+                "[$animation Error] `{}` isn't in {}",
+                anim.as_ref(),
+                other_qc.qc_file.display()
+            ),
+        };
+        animation.id = base_anim.id;
+        animation.dir = base_anim.dir.to_owned();
+        self.document.replace(animation.id, animation.block.clone());
+        let (start, end) = (animation.start, animation.end);
+        self.annotate(animation.id, "replaced", &other_qc.qc_file, start, end);
+        self.animations.insert(anim.as_ref().to_owned(), animation);
         Ok(other_qc.animations[anim.as_ref()].clone())
     }
 }
@@ -0,0 +1,114 @@
+// a handle to a block of lines in a Document, it stays the same when other blocks are
+// inserted or replaced
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BlockId(usize);
+
+// the lines of a qc file, split into blocks: every $sequence, $animation and $weightlist
+// block, and the lines between them
+// the blocks are a linked list, so a block can be inserted or replaced without moving the
+// lines after it or parsing the qc again
+#[derive(Debug, Default, Clone)]
+pub struct Document {
+    // the lines of each block, by id
+    blocks: Vec<Vec<String>>,
+    // the comment lines above each block, by id, they stay when the block is replaced
+    notes: Vec<Vec<String>>,
+    // the block before each block, by id
+    prev: Vec<Option<BlockId>>,
+    // the block after each block, by id
+    next: Vec<Option<BlockId>>,
+    first: Option<BlockId>,
+    last: Option<BlockId>,
+}
+
+impl Document {
+    // adds a block after the last one
+    pub fn push(&mut self, lines: Vec<String>) -> BlockId {
+        let id = self.add(lines, self.last, None);
+        match self.last {
+            Some(last) => self.next[last.0] = Some(id),
+            None => self.first = Some(id),
+        }
+        self.last = Some(id);
+        id
+    }
+
+    // adds a block right before another one
+    pub fn insert_before(&mut self, before: BlockId, lines: Vec<String>) -> BlockId {
+        let prev = self.prev[before.0];
+        let id = self.add(lines, prev, Some(before));
+        match prev {
+            Some(prev) => self.next[prev.0] = Some(id),
+            None => self.first = Some(id),
+        }
+        self.prev[before.0] = Some(id);
+        id
+    }
+
+    // adds a block right after another one
+    pub fn insert_after(&mut self, after: BlockId, lines: Vec<String>) -> BlockId {
+        let next = self.next[after.0];
+        let id = self.add(lines, Some(after), next);
+        match next {
+            Some(next) => self.prev[next.0] = Some(id),
+            None => self.last = Some(id),
+        }
+        self.next[after.0] = Some(id);
+        id
+    }
+
+    // changes the lines of a block, the number of lines can be different
+    pub fn replace(&mut self, id: BlockId, lines: Vec<String>) {
+        self.blocks[id.0] = lines;
+    }
+
+    pub fn block(&self, id: BlockId) -> &[String] {
+        &self.blocks[id.0]
+    }
+
+    // adds comment lines above a block, blocks inserted before it go above them
+    pub fn annotate(&mut self, id: BlockId, lines: Vec<String>) {
+        self.notes[id.0].extend(lines);
+    }
+
+    // the lines of every block, in order
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        let mut block = self.first;
+        while let Some(id) = block {
+            lines.extend(self.lines_of(id));
+            block = self.next[id.0];
+        }
+        lines
+    }
+
+    // the lines before a block and the lines from it to the end
+    pub fn split(&self, at: BlockId) -> (Vec<String>, Vec<String>) {
+        let (mut before, mut after) = (Vec::new(), Vec::new());
+        let mut block = self.first;
+        while let Some(id) = block {
+            if id == at {
+                std::mem::swap(&mut before, &mut after);
+            }
+            after.extend(self.lines_of(id));
+            block = self.next[id.0];
+        }
+        (before, after)
+    }
+
+    // a block's comments and lines
+    fn lines_of(&self, id: BlockId) -> impl Iterator<Item = String> + '_ {
+        self.notes[id.0]
+            .iter()
+            .chain(self.blocks[id.0].iter())
+            .cloned()
+    }
+
+    fn add(&mut self, lines: Vec<String>, prev: Option<BlockId>, next: Option<BlockId>) -> BlockId {
+        self.blocks.push(lines);
+        self.notes.push(Vec::new());
+        self.prev.push(prev);
+        self.next.push(next);
+        BlockId(self.blocks.len() - 1)
+    }
+}
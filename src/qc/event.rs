@@ -0,0 +1,134 @@
+use crate::qc::{path, QC};
+use crate::smd::{self, Smd};
+use anyhow::Result;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+// the events that play sounds, CL_EVENT_SOUND, SCRIPT_EVENT_SOUND and SCRIPT_EVENT_SOUND_VOICE
+const SOUND_EVENTS: [&str; 5] = ["5004", "1004", "1008", "AE_CL_PLAYSOUND", "AE_SV_PLAYSOUND"];
+
+// an `event` of a $sequence, e.g. `{ event 5004 18 "Weapon_Pistol.ClipIn" }`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Event {
+    // the event's number or name, e.g. `5004`, `AE_CL_CREATE_PARTICLE_EFFECT`
+    pub event: String,
+    // the frame it happens on
+    pub frame: i32,
+    // the options without quotes, e.g. the sound's name, empty if there are none
+    pub options: String,
+}
+
+impl Event {
+    // parses an event line, `{ event ... }` or just `event ...`, None if it isn't one
+    pub fn parse<T: AsRef<str>>(line: T) -> Option<Event> {
+        let line = line.as_ref().trim();
+        let line = line.strip_prefix('{').unwrap_or(line);
+        let line = line.strip_suffix('}').unwrap_or(line).trim();
+        let rest = line.strip_prefix("event")?;
+        if !rest.starts_with(char::is_whitespace) {
+            return None;
+        }
+
+        let mut line_split = rest.split_whitespace();
+        let event = line_split.next()?.trim_matches('"').to_owned();
+        let frame = line_split.next()?.trim_matches('"').parse::<i32>().ok()?;
+        let options = line_split.collect::<Vec<&str>>().join(" ");
+        Some(Event {
+            event,
+            frame,
+            options: options.trim_matches('"').to_owned(),
+        })
+    }
+
+    // true if the event plays a sound
+    pub fn is_sound(&self) -> bool {
+        SOUND_EVENTS
+            .iter()
+            .any(|event| event.eq_ignore_ascii_case(&self.event))
+    }
+
+    // moves the event to the same point of an animation with a different number of frames
+    pub fn rescale(&mut self, frames: usize, new_frames: usize) {
+        if frames > 1 && new_frames > 1 {
+            self.frame =
+                (self.frame as f64 * (new_frames - 1) as f64 / (frames - 1) as f64).round() as i32;
+        }
+    }
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.options.is_empty() {
+            write!(f, "{{ event {} {} }}", self.event, self.frame)
+        } else {
+            write!(
+                f,
+                "{{ event {} {} \"{}\" }}",
+                self.event, self.frame, self.options
+            )
+        }
+    }
+}
+
+impl QC {
+    // replaces the sound events of a $sequence in the document with events,
+    // they go where its first sound event was, or after its other options
+    pub fn replace_sound_events<T: AsRef<str>>(&mut self, seq: T, events: &[Event]) -> Result<()> {
+        if let Some(sequence) = self.sequences.get_mut(seq.as_ref()) {
+            let old_events = &mut sequence.options.events;
+            let first_sound = old_events
+                .iter()
+                .position(|event| event.is_sound())
+                .unwrap_or(old_events.len());
+            old_events.retain(|event| !event.is_sound());
+            // the events before the first sound event aren't sound events, so it's still there
+            old_events.splice(first_sound..first_sound, events.iter().cloned());
+        }
+        self.write_sequence(seq)
+    }
+
+    // the number of frames a $sequence plays, its `frames start end` or `frame start end` range,
+    // or the whole smd it plays directly or through an $animation when there's no range,
+    // relocated maps smds to the copies they were moved to
+    // None if the smd can't be found or read, or it's a dmx
+    pub fn frame_count(&self, seq: &str, relocated: &HashMap<PathBuf, PathBuf>) -> Option<usize> {
+        let seq = &self.sequences[seq];
+        let animation = if seq.uses_animation {
+            Some(self.animations.get(&seq.smd)?)
+        } else {
+            None
+        };
+        let range = frame_range(&seq.block)
+            .or_else(|| animation.and_then(|animation| frame_range(&animation.block)));
+        if let Some((start, end)) = range {
+            return Some((end - start).max(0) as usize + 1);
+        }
+        let smd = match animation {
+            Some(animation) => animation.smd_path(),
+            None => seq.smd_path(),
+        };
+        let smd = PathBuf::from(smd);
+        let qc_dir = self.qc_file.parent().unwrap_or_else(|| Path::new(""));
+        let file = path::find(qc_dir, relocated.get(&smd).unwrap_or(&smd))?;
+        if smd::is_dmx(&file) {
+            return None;
+        }
+        Some(Smd::new(file).ok()?.frame_count())
+    }
+}
+
+// the `frames start end` or `frame start end` line of a block
+fn frame_range(block: &[String]) -> Option<(i32, i32)> {
+    block.iter().find_map(|line| {
+        let words = line.split_whitespace().collect::<Vec<&str>>();
+        match words[..] {
+            [key, start, end]
+                if key.eq_ignore_ascii_case("frames") || key.eq_ignore_ascii_case("frame") =>
+            {
+                Some((start.parse().ok()?, end.parse().ok()?))
+            }
+            _ => None,
+        }
+    })
+}
@@ -8,7 +8,7 @@ macro_rules! dequote_nth {
             .unwrap()
             .chars()
             .filter(|&c| c != '"' && c != '\'')
-            .collect::<String>();
+            .collect::<String>()
     };
 }
 
@@ -22,7 +22,7 @@ macro_rules! dequote_next {
             .unwrap()
             .chars()
             .filter(|&c| c != '"' && c != '\'')
-            .collect::<String>();
+            .collect::<String>()
     };
 }
 
@@ -35,6 +35,6 @@ macro_rules! dequote {
         $line_split
             .chars()
             .filter(|&c| c != '"' && c != '\'')
-            .collect::<String>();
+            .collect::<String>()
     };
 }
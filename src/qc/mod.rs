@@ -1,10 +1,19 @@
+pub mod activity;
 pub mod animation;
+pub mod document;
+pub mod event;
 mod macros;
+pub mod options;
+pub mod path;
+pub mod placement;
+pub mod provenance;
 pub mod sequence;
 pub mod weightlist;
 
+use crate::dequote_nth;
 use anyhow::{Context, Result};
-use std::collections::HashMap;
+use document::BlockId;
+use indexmap::IndexMap;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
 use std::path::{Path, PathBuf};
@@ -13,12 +22,24 @@ use std::path::{Path, PathBuf};
 pub struct QC {
     // the path of the qc file
     pub qc_file: PathBuf,
-    // all of the $sequences in the qc file
-    pub sequences: HashMap<String, sequence::Sequence>,
-    // all of the $animations in the qc file
-    pub animations: HashMap<String, animation::Animation>,
-    // all of the $weightlists in the qc file
-    pub weightlists: HashMap<String, weightlist::Weightlist>,
+    // all of the $sequences in the qc file, in the order they're in the file
+    pub sequences: IndexMap<String, sequence::Sequence>,
+    // all of the $animations in the qc file, in the order they're in the file
+    pub animations: IndexMap<String, animation::Animation>,
+    // all of the $weightlists in the qc file, in the order they're in the file
+    pub weightlists: IndexMap<String, weightlist::Weightlist>,
+    // the bone names of the $definebone lines, in order, empty if the qc has none
+    pub bones: Vec<String>,
+    // the lines of the qc file, the blocks edited by the merge are changed here
+    pub document: document::Document,
+    // the $pushd folder at the end of the file, empty if none
+    pub end_dir: String,
+    // where the blocks appended to the qc go
+    pub placement: placement::Placement,
+    // where the appended blocks go when the placement isn't Kind, made by the first one
+    slots: Option<placement::Slots>,
+    // the recipe name or time in the comments above merged blocks, None if they don't get one
+    pub provenance: Option<String>,
 }
 
 pub enum QCCommand {
@@ -28,7 +49,7 @@ pub enum QCCommand {
 }
 
 pub enum QCBlock {
-    Sequence(sequence::Sequence),
+    Sequence(Box<sequence::Sequence>),
     Animation(animation::Animation),
     Weightlist(weightlist::Weightlist),
 }
@@ -40,12 +61,12 @@ impl QC {
                 .with_context(|| format!("Failed to read: {}", &qc_file.as_ref().display()))?,
         )
         .lines()
-        .filter_map(|line| line.ok())
-        .map(|line| line.trim().to_owned());
-        Ok(Self::parse(qc_file, reader)?)
+        .map_while(|line| line.ok());
+        Self::parse(qc_file, reader)
     }
 
-    // parses the qc file and returns a qc struct
+    // parses the qc file and returns a qc struct, the document keeps the lines as they are,
+    // indentation included
     pub fn parse<P, I>(qc_file: P, reader: I) -> Result<QC>
     where
         P: AsRef<Path>,
@@ -55,14 +76,29 @@ impl QC {
 
         // will store an entire $sequence, $animation, or $weightlist block
         let mut block: Vec<String> = Vec::new();
-        let mut qc: QC = Default::default();
-        qc.qc_file = PathBuf::from(qc_file.as_ref());
+        // the lines since the last block, they're a block of the document too
+        let mut text: Vec<String> = Vec::new();
+        let mut qc = QC {
+            qc_file: PathBuf::from(qc_file.as_ref()),
+            ..Default::default()
+        };
+        // the folders of $pushd, $popd removes the last one
+        let mut dirs: Vec<String> = Vec::new();
 
         loop {
-            let (idx, line) = match qc_data.next() {
+            let (idx, raw_line) = match qc_data.next() {
                 None => break,
                 Some(l) => l,
             };
+            let line = raw_line.trim();
+            let is_block = ["$sequence", "$animation", "$weightlist"]
+                .iter()
+                .any(|command| line.starts_with(command));
+            if is_block && !text.is_empty() {
+                qc.document.push(std::mem::take(&mut text));
+            } else if !is_block {
+                text.push(raw_line.to_owned());
+            }
             // if line is empty or is a comment, skip
             if line.is_empty()
                 || line.starts_with("//")
@@ -73,38 +109,123 @@ impl QC {
             } else if line.starts_with("/*") {
                 // skip lines until there's a `*/`
                 loop {
-                    let line = qc_data.next();
-                    if line.is_none() || line.unwrap().1.contains("*/") {
-                        break;
+                    match qc_data.next() {
+                        None => break,
+                        Some((_, line)) => {
+                            let end = line.contains("*/");
+                            text.push(line);
+                            if end {
+                                break;
+                            }
+                        }
                     }
                 }
+            } else if line.starts_with("$pushd") {
+                let mut line_split = line.split_whitespace();
+                if line_split.clone().count() > 1 {
+                    dirs.push(dequote_nth!(line_split, 1));
+                }
+            } else if line.starts_with("$popd") {
+                dirs.pop();
+            } else if line.starts_with("$definebone") {
+                let mut line_split = line.split_whitespace();
+                if line_split.clone().count() > 1 {
+                    qc.bones.push(dequote_nth!(line_split, 1));
+                }
             } else if line.starts_with("$sequence") {
-                if let QCBlock::Sequence(seq) =
-                    Self::find_block(QCCommand::Sequence, &mut qc_data, &mut block, line, idx)?
-                {
-                    qc.sequences.insert(seq.name.clone(), seq);
+                if let QCBlock::Sequence(mut seq) = Self::find_block(
+                    QCCommand::Sequence,
+                    &mut qc_data,
+                    &mut block,
+                    raw_line.clone(),
+                    idx,
+                )? {
+                    seq.dir = dirs.join("/");
+                    seq.id = qc.document.push(block.clone());
+                    qc.sequences.insert(seq.name.clone(), *seq);
                     // clears the block for the next qc command block
                     block.clear();
                 }
             } else if line.starts_with("$animation") {
-                if let QCBlock::Animation(anim) =
-                    Self::find_block(QCCommand::Animation, &mut qc_data, &mut block, line, idx)?
-                {
+                if let QCBlock::Animation(mut anim) = Self::find_block(
+                    QCCommand::Animation,
+                    &mut qc_data,
+                    &mut block,
+                    raw_line.clone(),
+                    idx,
+                )? {
+                    anim.dir = dirs.join("/");
+                    anim.id = qc.document.push(block.clone());
                     qc.animations.insert(anim.name.clone(), anim);
                     block.clear();
                 }
             } else if line.starts_with("$weightlist") {
-                if let QCBlock::Weightlist(weight) =
-                    Self::find_block(QCCommand::Weightlist, &mut qc_data, &mut block, line, idx)?
-                {
+                if let QCBlock::Weightlist(mut weight) = Self::find_block(
+                    QCCommand::Weightlist,
+                    &mut qc_data,
+                    &mut block,
+                    raw_line.clone(),
+                    idx,
+                )? {
+                    weight.dir = dirs.join("/");
+                    weight.id = qc.document.push(block.clone());
                     qc.weightlists.insert(weight.name.clone(), weight);
                     block.clear();
                 }
             }
         }
+        if !text.is_empty() {
+            qc.document.push(text);
+        }
+        qc.end_dir = dirs.join("/");
+        qc.classify_references();
         Ok(qc)
     }
 
+    // studiomdl uses an $animation if one has the name, otherwise it looks for a file
+    // Sequence::parse can only guess from the `.smd`/`.dmx` extension, this checks both
+    fn classify_references(&mut self) {
+        let mut qc_dir = self.qc_file.clone();
+        qc_dir.pop();
+        for seq in self.sequences.values_mut() {
+            classify_reference(seq, &self.animations, &qc_dir);
+        }
+    }
+
+    // classifies a $sequence that isn't in the qc yet, like classify_references
+    fn classify_sequence(&self, seq: &mut sequence::Sequence) {
+        let mut qc_dir = self.qc_file.clone();
+        qc_dir.pop();
+        classify_reference(seq, &self.animations, &qc_dir);
+    }
+
+    // gets the smds that can't be found, as `(block name, qc path, files that were tried)`
+    // includes the $sequences whose reference is neither an $animation nor a file
+    pub fn missing_smds(&self) -> Vec<(String, String, Vec<PathBuf>)> {
+        let mut qc_dir = self.qc_file.clone();
+        qc_dir.pop();
+        let mut missing = self
+            .sequences
+            .values()
+            .filter(|seq| !seq.smd.is_empty())
+            .filter(|seq| !seq.uses_animation || !self.animations.contains_key(&seq.smd))
+            .map(|seq| (&seq.name, seq.smd_path()))
+            .chain(
+                self.animations
+                    .values()
+                    .filter(|anim| !anim.smd.is_empty())
+                    .map(|anim| (&anim.name, anim.smd_path())),
+            )
+            .filter(|(_, smd)| path::find(&qc_dir, smd).is_none())
+            .map(|(name, smd)| {
+                let tried = path::candidates(&qc_dir, &smd);
+                (name.to_owned(), smd, tried)
+            })
+            .collect::<Vec<(String, String, Vec<PathBuf>)>>();
+        missing.sort();
+        missing
+    }
+
     // finds the qc command block
     fn find_block<I: Iterator<Item = (usize, String)>>(
         qc_command: QCCommand,
@@ -119,43 +240,208 @@ impl QC {
         // checks if block is a single-line block, assumes only $animation will be single-lined
         // assumes single-lined $animation block won't have braces
         if !line.contains('{') && matches!(qc_command, QCCommand::Animation) {
-            return Ok(QCBlock::Animation(animation::Animation::parse(
-                &block, idx,
-            )?));
+            return Ok(QCBlock::Animation(animation::Animation::parse(block, idx)?));
         }
+        // the `{` and `}` of events split over lines are inside the block
+        let mut depth = braces(&line);
         loop {
             let (_, line) = match qc_data.next() {
                 None => break,
                 Some(l) => l,
             };
+            depth += braces(&line);
             // assumes `}` is on a line by itself
-            if line.starts_with('}') {
+            if line.trim_start().starts_with('}') && depth <= 0 {
                 block.push(line);
                 break;
             }
             block.push(line);
         }
         Ok(match qc_command {
-            QCCommand::Sequence => QCBlock::Sequence(sequence::Sequence::parse(&block, idx)?),
-            QCCommand::Animation => QCBlock::Animation(animation::Animation::parse(&block, idx)?),
+            QCCommand::Sequence => {
+                QCBlock::Sequence(Box::new(sequence::Sequence::parse(block, idx)?))
+            }
+            QCCommand::Animation => QCBlock::Animation(animation::Animation::parse(block, idx)?),
             QCCommand::Weightlist => {
-                QCBlock::Weightlist(weightlist::Weightlist::parse(&block, idx)?)
+                QCBlock::Weightlist(weightlist::Weightlist::parse(block, idx)?)
             }
         })
     }
 
-    // gets all smd path in qc, assumes smds are relative
+    // gets all smd path in qc, assumes smds are relative, includes the $pushd folder
     pub fn get_smds(&self) -> Vec<PathBuf> {
+        self.get_smds_except(&[])
+    }
+
+    // gets the smds the qc still uses if the sequences are replaced, like get_smds
+    pub fn get_smds_except(&self, sequences: &[String]) -> Vec<PathBuf> {
         self.sequences
             .iter()
+            .filter(|(name, _)| !sequences.contains(name))
             .filter(|(_, seq)| !seq.uses_animation && !seq.smd.is_empty())
-            .map(|(_, seq)| PathBuf::from(&seq.smd))
+            .map(|(_, seq)| PathBuf::from(seq.smd_path()))
             .chain(
                 self.animations
                     .iter()
                     .filter(|(_, anim)| !anim.smd.is_empty())
-                    .map(|(_, anim)| PathBuf::from(&anim.smd)),
+                    .map(|(_, anim)| PathBuf::from(anim.smd_path())),
             )
             .collect::<Vec<PathBuf>>()
     }
+
+    // changes every reference to the smd `from` to `to`, including the block lines
+    // both are relative to the qc file's folder, like get_smds
+    pub fn relocate_smd<T: AsRef<str>>(&mut self, from: T, to: T) {
+        let (from, to) = (from.as_ref(), to.as_ref());
+        for seq in self.sequences.values_mut() {
+            if !seq.uses_animation && !seq.smd.is_empty() && seq.smd_path() == from {
+                let new_smd = path::relative_to(seq.dir.as_str(), to);
+                relocate_block(&mut seq.block, &seq.smd, &new_smd);
+                seq.smd = new_smd;
+                seq.parse_options();
+            }
+        }
+        for anim in self.animations.values_mut() {
+            if !anim.smd.is_empty() && anim.smd_path() == from {
+                let new_smd = path::relative_to(anim.dir.as_str(), to);
+                relocate_block(&mut anim.block, &anim.smd, &new_smd);
+                anim.smd = new_smd;
+            }
+        }
+    }
+
+    // points one $sequence at a different smd, the other blocks that use its smd keep it,
+    // e.g. so it can have its own resampled copy, `to` is relative to the qc file's folder
+    pub fn relocate_sequence_smd<T: AsRef<str>>(&mut self, seq: T, to: T) {
+        if let Some(seq) = self.sequences.get_mut(seq.as_ref()) {
+            let new_smd = path::relative_to(seq.dir.as_str(), to.as_ref());
+            relocate_block(&mut seq.block, &seq.smd, &new_smd);
+            seq.smd = new_smd;
+            seq.parse_options();
+            self.document.replace(seq.id, seq.block.clone());
+        }
+    }
+
+    // same as relocate_smd, but also changes the lines of the blocks in the document
+    pub fn relocate_smd_in<T: AsRef<str>>(&mut self, from: T, to: T) {
+        let (from, to) = (from.as_ref(), to.as_ref());
+        let ids = self
+            .sequences
+            .values()
+            .filter(|seq| !seq.uses_animation && !seq.smd.is_empty() && seq.smd_path() == from)
+            .map(|seq| seq.id)
+            .chain(
+                self.animations
+                    .values()
+                    .filter(|anim| !anim.smd.is_empty() && anim.smd_path() == from)
+                    .map(|anim| anim.id),
+            )
+            .collect::<Vec<BlockId>>();
+        self.relocate_smd(from, to);
+        for seq in self.sequences.values().filter(|seq| ids.contains(&seq.id)) {
+            self.document.replace(seq.id, seq.block.clone());
+        }
+        for anim in self
+            .animations
+            .values()
+            .filter(|anim| ids.contains(&anim.id))
+        {
+            self.document.replace(anim.id, anim.block.clone());
+        }
+    }
+
+    // blocks copied from other_qc keep their smd path relative to other_qc's $pushd folder,
+    // this makes the named blocks point to the same file from the $pushd folder they're in now
+    pub fn rebase_smds(&mut self, other_qc: &Self, names: &[String]) {
+        for name in names {
+            if let (Some(seq), Some(other_seq)) =
+                (self.sequences.get_mut(name), other_qc.sequences.get(name))
+            {
+                if !seq.uses_animation && seq.smd_path() != other_seq.smd_path() {
+                    let new_smd = path::relative_to(seq.dir.clone(), other_seq.smd_path());
+                    relocate_block(&mut seq.block, &seq.smd, &new_smd);
+                    seq.smd = new_smd;
+                    seq.parse_options();
+                    self.document.replace(seq.id, seq.block.clone());
+                }
+            }
+            if let (Some(anim), Some(other_anim)) =
+                (self.animations.get_mut(name), other_qc.animations.get(name))
+            {
+                if anim.smd_path() != other_anim.smd_path() {
+                    let new_smd = path::relative_to(anim.dir.clone(), other_anim.smd_path());
+                    relocate_block(&mut anim.block, &anim.smd, &new_smd);
+                    anim.smd = new_smd;
+                    self.document.replace(anim.id, anim.block.clone());
+                }
+            }
+        }
+    }
+}
+
+fn classify_reference(
+    seq: &mut sequence::Sequence,
+    animations: &IndexMap<String, animation::Animation>,
+    qc_dir: &Path,
+) {
+    if seq.smd.is_empty() {
+        return;
+    }
+    if animations.contains_key(&seq.smd) {
+        seq.uses_animation = true;
+    } else if path::find(qc_dir, seq.smd_path()).is_some() {
+        seq.uses_animation = false;
+    }
+}
+
+// the number of `{` minus the number of `}` in a line, without its `//` comment
+fn braces(line: &str) -> i32 {
+    let line = line.split_once("//").map_or(line, |(line, _)| line);
+    line.matches('{').count() as i32 - line.matches('}').count() as i32
+}
+
+// the whitespace at the start of a line, kept when a line of a block is written again
+fn indent(line: &str) -> &str {
+    &line[..line.len() - line.trim_start().len()]
+}
+
+// renames the block a first line like `$sequence "name" ...` starts, the name can be quoted
+// the line's indentation is kept
+fn rename_header(line: &str, new_name: &str) -> String {
+    let indent = indent(line);
+    let line = line.trim_start();
+    let (command, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    let rest = rest.trim_start();
+    let rest = match rest.strip_prefix('"') {
+        Some(rest) => rest.split_once('"').map_or("", |(_, rest)| rest),
+        None => rest
+            .split_once(char::is_whitespace)
+            .map_or("", |(_, rest)| rest),
+    };
+    format!(
+        "{}{} \"{}\" {}",
+        indent,
+        command,
+        new_name,
+        rest.trim_start()
+    )
+    .trim_end()
+    .to_owned()
+}
+
+// replaces the smd path in the block lines, quoted or not
+fn relocate_block(block: &mut [String], from: &str, to: &str) {
+    for line in block.iter_mut() {
+        let quoted = format!("\"{}\"", from);
+        if line.contains(&quoted) {
+            *line = line.replace(&quoted, &format!("\"{}\"", to));
+        } else if line.split_whitespace().any(|word| word == from) {
+            *line = indent(line).to_owned()
+                + &line
+                    .split_whitespace()
+                    .map(|word| if word == from { to } else { word })
+                    .collect::<Vec<&str>>()
+                    .join(" ");
+        }
+    }
 }
@@ -0,0 +1,501 @@
+use crate::qc::event::Event;
+use crate::qc::{indent, QC};
+use anyhow::{bail, Result};
+use std::fmt;
+
+// the options an OptionOverride can change
+const OPTION_KEYS: [&str; 15] = [
+    "activity",
+    "fps",
+    "loop",
+    "snap",
+    "fadein",
+    "fadeout",
+    "node",
+    "blend",
+    "blendwidth",
+    "delta",
+    "hidden",
+    "realtime",
+    "localhierarchy",
+    "frames",
+    "events",
+];
+
+// `activity "ACT_NAME" weight`, sequences with the same activity are picked at random
+// by weight
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Activity {
+    pub name: String,
+    pub weight: i32,
+}
+
+// `blend "parameter" min max`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Blend {
+    pub parameter: String,
+    pub min: f64,
+    pub max: f64,
+}
+
+// `localhierarchy "bone" "new parent" [range start peak tail end]`
+#[derive(Debug, Clone, PartialEq)]
+pub struct LocalHierarchy {
+    pub bone: String,
+    pub parent: String,
+    // the frames the new parent blends in and out, `[start, peak, tail, end]`
+    pub range: Option<[f64; 4]>,
+}
+
+// changes an option of a merged $sequence, `key=value` or `key=base`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OptionOverride {
+    pub key: String,
+    // the value with `:` between words, e.g. `0:20` for `frames 0 20`,
+    // None takes the option from the base's sequence
+    pub value: Option<String>,
+}
+
+impl OptionOverride {
+    // parses `key=value`, `none` unsets the option and `base` uses the base's option
+    pub fn parse<T: AsRef<str>>(option: T) -> Result<OptionOverride> {
+        let option = option.as_ref();
+        let (key, value) = match option.split_once('=') {
+            Some((key, value)) if !key.is_empty() && !value.is_empty() => (key, value),
+            _ => bail!("Options must be `option=value`: `{}`", option),
+        };
+        let key = key.to_lowercase();
+        if !OPTION_KEYS.contains(&key.as_str()) {
+            bail!(
+                "`{}` isn't an option that can be changed, use one of: {}",
+                key,
+                OPTION_KEYS.join(", ")
+            );
+        }
+        Ok(OptionOverride {
+            key,
+            value: match value {
+                "base" => None,
+                value => Some(value.to_owned()),
+            },
+        })
+    }
+}
+
+impl fmt::Display for OptionOverride {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}={}",
+            self.key,
+            self.value.as_deref().unwrap_or("base")
+        )
+    }
+}
+
+// the options of a $sequence block that have typed fields
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OptionKind {
+    // the options that can be used more than once have the index of the value
+    Activity(usize),
+    Fps,
+    Loop,
+    Snap,
+    FadeIn,
+    FadeOut,
+    Node,
+    Blend(usize),
+    BlendWidth,
+    Delta,
+    Hidden,
+    Realtime,
+    LocalHierarchy(usize),
+    Frames,
+    Event(usize),
+}
+
+// a line between the first line of the block and `}`
+#[derive(Debug, Clone, PartialEq)]
+enum OptionLine {
+    // written from the typed field once its value changes, as it was until then
+    Known {
+        kind: OptionKind,
+        // the option's lines, an event can be `{`, `event ...` and `}` on their own lines
+        lines: Vec<String>,
+        // the line write_option gave for the value it was parsed with
+        value: Option<String>,
+        // the whitespace before the option and its `//` comment, they're kept when the
+        // line is written again
+        indent: String,
+        comment: Option<String>,
+    },
+    // written as-is, e.g. the smd, comments and options without a field
+    Verbatim(String),
+}
+
+// the options of a $sequence, write_lines regenerates the block's lines from them
+// options without a field are kept as they are
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct SequenceOptions {
+    // the `activity`s, studiomdl only uses one but the others are kept
+    pub activities: Vec<Activity>,
+    // `fps`, studiomdl uses 30 if it's None
+    pub fps: Option<f64>,
+    // `loop`
+    pub looping: bool,
+    pub snap: bool,
+    pub fadein: Option<f64>,
+    pub fadeout: Option<f64>,
+    // `node`, the transition node, e.g. `raised`
+    pub node: Option<String>,
+    pub blend: Vec<Blend>,
+    pub blendwidth: Option<u32>,
+    pub delta: bool,
+    pub hidden: bool,
+    pub realtime: bool,
+    pub localhierarchy: Vec<LocalHierarchy>,
+    // `frames start end`
+    pub frames: Option<(i32, i32)>,
+    // the `event`s, in the order they're in the block
+    pub events: Vec<Event>,
+    // the lines between the first line of the block and `}`, in order
+    lines: Vec<OptionLine>,
+}
+
+impl SequenceOptions {
+    // parses the lines between the first line of a $sequence block and `}`
+    pub fn parse(lines: &[String]) -> SequenceOptions {
+        let mut options = SequenceOptions::default();
+        let mut i = 0;
+        while i < lines.len() {
+            // an event split over lines is one option
+            let event = lines[i..].get(..3).filter(|event| {
+                event[0].trim() == "{"
+                    && event[2].trim() == "}"
+                    && Event::parse(split_comment(&event[1]).0).is_some()
+            });
+            let (option_lines, line) = match event {
+                Some(event) => (event, &event[1]),
+                None => (&lines[i..=i], &lines[i]),
+            };
+            i += option_lines.len();
+
+            let (option, comment) = split_comment(line);
+            let option = options.parse_line(option);
+            options.lines.push(match option {
+                Some(kind) => OptionLine::Known {
+                    kind,
+                    lines: option_lines.to_vec(),
+                    value: options.write_option(kind),
+                    indent: indent(&option_lines[0]).to_owned(),
+                    comment: comment.map(|comment| comment.to_owned()),
+                },
+                None => OptionLine::Verbatim(line.to_owned()),
+            });
+        }
+        options
+    }
+
+    // sets the field of an option line, None if it doesn't have a field or can't be parsed
+    fn parse_line(&mut self, line: &str) -> Option<OptionKind> {
+        if let Some(event) = Event::parse(line) {
+            self.events.push(event);
+            return Some(OptionKind::Event(self.events.len() - 1));
+        }
+        let words = line
+            .split_whitespace()
+            .map(|word| word.trim_matches('"'))
+            .collect::<Vec<&str>>();
+        let option = words.first()?.to_lowercase();
+        let number = |i: usize| words.get(i)?.parse::<f64>().ok();
+
+        match option.as_str() {
+            "activity" if words.len() == 3 => {
+                self.activities.push(Activity {
+                    name: words[1].to_owned(),
+                    weight: words[2].parse().ok()?,
+                });
+                Some(OptionKind::Activity(self.activities.len() - 1))
+            }
+            "fps" if words.len() == 2 => {
+                self.fps = Some(number(1)?);
+                Some(OptionKind::Fps)
+            }
+            "loop" if words.len() == 1 => {
+                self.looping = true;
+                Some(OptionKind::Loop)
+            }
+            "snap" if words.len() == 1 => {
+                self.snap = true;
+                Some(OptionKind::Snap)
+            }
+            "fadein" if words.len() == 2 => {
+                self.fadein = Some(number(1)?);
+                Some(OptionKind::FadeIn)
+            }
+            "fadeout" if words.len() == 2 => {
+                self.fadeout = Some(number(1)?);
+                Some(OptionKind::FadeOut)
+            }
+            "node" if words.len() == 2 => {
+                self.node = Some(words[1].to_owned());
+                Some(OptionKind::Node)
+            }
+            "blend" if words.len() == 4 => {
+                self.blend.push(Blend {
+                    parameter: words[1].to_owned(),
+                    min: number(2)?,
+                    max: number(3)?,
+                });
+                Some(OptionKind::Blend(self.blend.len() - 1))
+            }
+            "blendwidth" if words.len() == 2 => {
+                self.blendwidth = Some(words[1].parse().ok()?);
+                Some(OptionKind::BlendWidth)
+            }
+            "delta" if words.len() == 1 => {
+                self.delta = true;
+                Some(OptionKind::Delta)
+            }
+            "hidden" if words.len() == 1 => {
+                self.hidden = true;
+                Some(OptionKind::Hidden)
+            }
+            "realtime" if words.len() == 1 => {
+                self.realtime = true;
+                Some(OptionKind::Realtime)
+            }
+            "localhierarchy" if words.len() == 3 || words.len() == 8 && words[3] == "range" => {
+                let range = if words.len() == 8 {
+                    Some([number(4)?, number(5)?, number(6)?, number(7)?])
+                } else {
+                    None
+                };
+                self.localhierarchy.push(LocalHierarchy {
+                    bone: words[1].to_owned(),
+                    parent: words[2].to_owned(),
+                    range,
+                });
+                Some(OptionKind::LocalHierarchy(self.localhierarchy.len() - 1))
+            }
+            "frames" if words.len() == 3 => {
+                self.frames = Some((words[1].parse().ok()?, words[2].parse().ok()?));
+                Some(OptionKind::Frames)
+            }
+            _ => None,
+        }
+    }
+
+    // changes an option, base is the options of the base's sequence if it has one
+    pub fn apply(&mut self, option: &OptionOverride, base: Option<&SequenceOptions>) -> Result<()> {
+        match (&option.value, base) {
+            (Some(value), _) => self.set(&option.key, value),
+            (None, Some(base)) => self.copy(&option.key, base),
+            (None, None) => bail!("There's no base sequence to take `{}` from", option.key),
+        }
+    }
+
+    // sets an option from an OptionOverride's value, flags are `true` or `false`
+    fn set(&mut self, key: &str, value: &str) -> Result<()> {
+        let mut options = self.clone();
+        options.copy(key, &SequenceOptions::default())?;
+        let line = match value {
+            "none" | "false" => None,
+            "true" => Some(key.to_owned()),
+            value => Some(format!("{} {}", key, value.replace(':', " "))),
+        };
+        if let Some(line) = line {
+            if options.parse_line(&line).is_none() {
+                bail!("Weird value for `{}`: `{}`", key, value);
+            }
+        }
+        *self = options;
+        Ok(())
+    }
+
+    // takes an option from other, it's unset if other doesn't have it
+    fn copy(&mut self, key: &str, other: &SequenceOptions) -> Result<()> {
+        match key {
+            "activity" => self.activities = other.activities.clone(),
+            "fps" => self.fps = other.fps,
+            "loop" => self.looping = other.looping,
+            "snap" => self.snap = other.snap,
+            "fadein" => self.fadein = other.fadein,
+            "fadeout" => self.fadeout = other.fadeout,
+            "node" => self.node = other.node.clone(),
+            "blend" => self.blend = other.blend.clone(),
+            "blendwidth" => self.blendwidth = other.blendwidth,
+            "delta" => self.delta = other.delta,
+            "hidden" => self.hidden = other.hidden,
+            "realtime" => self.realtime = other.realtime,
+            "localhierarchy" => self.localhierarchy = other.localhierarchy.clone(),
+            "frames" => self.frames = other.frames,
+            "events" => self.events = other.events.clone(),
+            _ => bail!("`{}` isn't an option that can be changed", key),
+        }
+        Ok(())
+    }
+
+    // the lines of the options, options that were added after parsing go at the end
+    // with the indent of the first option
+    pub fn write_lines(&self) -> Vec<String> {
+        let mut written = Vec::new();
+        let mut lines = Vec::new();
+        for line in &self.lines {
+            match line {
+                OptionLine::Known {
+                    kind,
+                    lines: old_lines,
+                    value,
+                    indent,
+                    comment,
+                } => {
+                    written.push(*kind);
+                    let line = self.write_option(*kind);
+                    if line == *value {
+                        lines.extend(old_lines.iter().cloned());
+                    } else if let Some(line) = line {
+                        lines.push(match comment {
+                            Some(comment) => format!("{}{} {}", indent, line, comment),
+                            None => format!("{}{}", indent, line),
+                        });
+                    }
+                }
+                OptionLine::Verbatim(line) => lines.push(line.to_owned()),
+            }
+        }
+
+        let kinds = (0..self.activities.len())
+            .map(OptionKind::Activity)
+            .chain(
+                [
+                    OptionKind::Fps,
+                    OptionKind::Loop,
+                    OptionKind::Snap,
+                    OptionKind::FadeIn,
+                    OptionKind::FadeOut,
+                    OptionKind::Node,
+                ]
+                .iter()
+                .copied(),
+            )
+            .chain((0..self.blend.len()).map(OptionKind::Blend))
+            .chain(
+                [
+                    OptionKind::BlendWidth,
+                    OptionKind::Delta,
+                    OptionKind::Hidden,
+                    OptionKind::Realtime,
+                ]
+                .iter()
+                .copied(),
+            )
+            .chain((0..self.localhierarchy.len()).map(OptionKind::LocalHierarchy))
+            .chain(std::iter::once(OptionKind::Frames))
+            .chain((0..self.events.len()).map(OptionKind::Event));
+        // the added options get the indent of the block's first option
+        let added_indent = self
+            .lines
+            .first()
+            .map(|line| match line {
+                OptionLine::Known { indent, .. } => indent.as_str(),
+                OptionLine::Verbatim(line) => indent(line),
+            })
+            .unwrap_or("");
+        for kind in kinds {
+            if !written.contains(&kind) {
+                lines.extend(
+                    self.write_option(kind)
+                        .map(|line| format!("{}{}", added_indent, line)),
+                );
+            }
+        }
+        lines
+    }
+
+    // the line of an option, None if it isn't set
+    fn write_option(&self, kind: OptionKind) -> Option<String> {
+        let flag = |set: bool, name: &str| if set { Some(name.to_owned()) } else { None };
+        match kind {
+            OptionKind::Activity(i) => self.activities.get(i).map(|activity| activity.to_string()),
+            OptionKind::Fps => self.fps.map(|fps| format!("fps {}", fps)),
+            OptionKind::Loop => flag(self.looping, "loop"),
+            OptionKind::Snap => flag(self.snap, "snap"),
+            OptionKind::FadeIn => self.fadein.map(|fadein| format!("fadein {}", fadein)),
+            OptionKind::FadeOut => self.fadeout.map(|fadeout| format!("fadeout {}", fadeout)),
+            OptionKind::Node => self.node.as_ref().map(|node| format!("node \"{}\"", node)),
+            OptionKind::Blend(i) => self.blend.get(i).map(|blend| blend.to_string()),
+            OptionKind::BlendWidth => self
+                .blendwidth
+                .map(|blendwidth| format!("blendwidth {}", blendwidth)),
+            OptionKind::Delta => flag(self.delta, "delta"),
+            OptionKind::Hidden => flag(self.hidden, "hidden"),
+            OptionKind::Realtime => flag(self.realtime, "realtime"),
+            OptionKind::LocalHierarchy(i) => self
+                .localhierarchy
+                .get(i)
+                .map(|localhierarchy| localhierarchy.to_string()),
+            OptionKind::Frames => self
+                .frames
+                .map(|(start, end)| format!("frames {} {}", start, end)),
+            OptionKind::Event(i) => self.events.get(i).map(|event| event.to_string()),
+        }
+    }
+}
+
+impl QC {
+    // changes the options of a $sequence in the document, base is the options of the base's
+    // sequence with the same name
+    pub fn override_options<T: AsRef<str>>(
+        &mut self,
+        seq: T,
+        overrides: &[OptionOverride],
+        base: Option<&SequenceOptions>,
+    ) -> Result<()> {
+        if let Some(sequence) = self.sequences.get_mut(seq.as_ref()) {
+            let mut options = sequence.options.clone();
+            for option in overrides {
+                options.apply(option, base)?;
+            }
+            sequence.options = options;
+        }
+        self.write_sequence(seq)
+    }
+}
+
+// splits a line at its `//` comment, the comment keeps the `//`
+fn split_comment(line: &str) -> (&str, Option<&str>) {
+    let mut quoted = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            '/' if !quoted && line[i + 1..].starts_with('/') => {
+                return (line[..i].trim_end(), Some(&line[i..]));
+            }
+            _ => (),
+        }
+    }
+    (line, None)
+}
+
+impl fmt::Display for Activity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "activity \"{}\" {}", self.name, self.weight)
+    }
+}
+
+impl fmt::Display for Blend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "blend \"{}\" {} {}", self.parameter, self.min, self.max)
+    }
+}
+
+impl fmt::Display for LocalHierarchy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "localhierarchy \"{}\" \"{}\"", self.bone, self.parent)?;
+        if let Some([start, peak, tail, end]) = self.range {
+            write!(f, " range {} {} {} {}", start, peak, tail, end)?;
+        }
+        Ok(())
+    }
+}
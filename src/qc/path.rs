@@ -0,0 +1,154 @@
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+// turns a path from a qc file into a path for this os, the qc keeps the original spelling
+// e.g. `c_scout_animations_anims\b_idle.smd` -> `c_scout_animations_anims/b_idle.smd`
+pub fn normalize<P: AsRef<Path>>(path: P) -> PathBuf {
+    path.as_ref()
+        .to_string_lossy()
+        .split(['\\', '/'])
+        .filter(|part| !part.is_empty() && *part != ".")
+        .collect::<PathBuf>()
+}
+
+// what two qc paths are compared by, they name the same file if their keys are the same
+// the separators are normalized and case is ignored, like find does
+pub fn key<P: AsRef<Path>>(path: P) -> String {
+    normalize(path).to_string_lossy().to_lowercase()
+}
+
+// true if one of paths names the same file as path, see key
+pub fn contains<'a, I, P>(paths: I, path: P) -> bool
+where
+    I: IntoIterator<Item = &'a PathBuf>,
+    P: AsRef<Path>,
+{
+    let path = key(path);
+    paths.into_iter().any(|other| key(other) == path)
+}
+
+// true if a qc path names an animation file, `.smd` or `.dmx` ignoring case,
+// otherwise it's the name of an $animation
+pub fn is_animation_file<T: AsRef<str>>(path: T) -> bool {
+    let path = path.as_ref().to_lowercase();
+    path.contains(".smd") || path.contains(".dmx")
+}
+
+// the files studiomdl tries for a qc path, relative to dir
+// the path as-is, then with `.smd` and `.dmx` if it has no extension
+pub fn candidates<P, Q>(dir: P, path: Q) -> Vec<PathBuf>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    let normalized = normalize(&path);
+    let mut candidates = vec![dir.as_ref().join(&normalized)];
+    if normalized.extension().is_none() {
+        for extension in &["smd", "dmx"] {
+            let mut with_extension = normalized.clone().into_os_string();
+            with_extension.push(".");
+            with_extension.push(extension);
+            candidates.push(dir.as_ref().join(with_extension));
+        }
+    }
+    candidates
+}
+
+// finds the file a qc path points to, relative to dir
+// tries each candidate as-is and then ignoring case
+pub fn find<P, Q>(dir: P, path: Q) -> Option<PathBuf>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    for candidate in candidates(&dir, &path) {
+        if candidate.is_file() {
+            return Some(candidate);
+        }
+        match find_ignore_case(dir.as_ref(), candidate.strip_prefix(&dir).ok()?) {
+            Some(found) if found.is_file() => return Some(found),
+            _ => {}
+        }
+    }
+    None
+}
+
+// same as find, but returns the normalized path if the file can't be found,
+// so errors show a sensible path
+pub fn resolve<P, Q>(dir: P, path: Q) -> PathBuf
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    find(&dir, &path).unwrap_or_else(|| dir.as_ref().join(normalize(&path)))
+}
+
+// makes a qc path relative to a $pushd folder, keeps the path's separator
+// e.g. relative_to("anims", "anims/p_draw.smd") => `p_draw.smd`
+// e.g. relative_to("anims", "other/p_draw.smd") => `../other/p_draw.smd`
+pub fn relative_to<T: AsRef<str>>(dir: T, path: T) -> String {
+    let (dir, path) = (dir.as_ref(), path.as_ref());
+    if dir.is_empty() {
+        return path.to_owned();
+    }
+    let separator = if path.contains('\\') { "\\" } else { "/" };
+    let dir_parts = normalize(dir);
+    let path_parts = normalize(path);
+    match path_parts.strip_prefix(&dir_parts) {
+        Ok(rest) => rest
+            .iter()
+            .map(|part| part.to_string_lossy())
+            .collect::<Vec<_>>()
+            .join(separator),
+        Err(_) => dir_parts
+            .iter()
+            .map(|_| "..".into())
+            .chain(path_parts.iter().map(|part| part.to_string_lossy()))
+            .collect::<Vec<_>>()
+            .join(separator),
+    }
+}
+
+// where a qc path is written to relative to an output folder, keeps the qc's spelling
+// but adds the extension of the file it resolved to if the qc left it out
+pub fn output_path<P, Q>(path: P, resolved: Q) -> PathBuf
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    let mut normalized = normalize(path);
+    if normalized.extension().is_none() {
+        if let Some(extension) = resolved.as_ref().extension() {
+            let mut with_extension = normalized.into_os_string();
+            with_extension.push(".");
+            with_extension.push(extension);
+            normalized = PathBuf::from(with_extension);
+        }
+    }
+    normalized
+}
+
+// walks the path one component at a time, matching each one ignoring case
+fn find_ignore_case(dir: &Path, path: &Path) -> Option<PathBuf> {
+    let mut found = dir.to_path_buf();
+    for component in path.components() {
+        let name = match component {
+            Component::Normal(name) => name.to_string_lossy().to_lowercase(),
+            Component::ParentDir => {
+                found.push("..");
+                continue;
+            }
+            _ => continue,
+        };
+        found = fs::read_dir(&found)
+            .ok()?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .find(|entry| {
+                entry
+                    .file_name()
+                    .is_some_and(|entry| entry.to_string_lossy().to_lowercase() == name)
+            })?;
+    }
+    Some(found)
+}
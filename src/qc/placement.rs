@@ -0,0 +1,178 @@
+use crate::qc::document::BlockId;
+use crate::qc::{path, QCCommand, QC};
+use anyhow::{bail, Result};
+use std::path::Path;
+use std::str::FromStr;
+
+// where the blocks appended to a qc go
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub enum Placement {
+    // $sequences after the last $sequence, $animations before the first $animation and
+    // $weightlists before the first $weightlist
+    #[default]
+    Kind,
+    // at the end of the file
+    End,
+    // at the end of the file under a `// merged from <donor>` banner
+    Group,
+    // after the block with the name
+    Anchor(String),
+    // in a generated `<qc name>_merged.qci` next to the qc, the qc $includes it at its end
+    // appended blocks only add the `$include` line, replaced ones are still changed in the qc,
+    // it's refused if the qc already has the `$include`
+    Qci,
+}
+
+impl FromStr for Placement {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "kind" => Ok(Placement::Kind),
+            "end" => Ok(Placement::End),
+            "group" => Ok(Placement::Group),
+            "qci" => Ok(Placement::Qci),
+            _ => match s.strip_prefix("anchor:") {
+                Some(name) if !name.is_empty() => Ok(Placement::Anchor(name.to_owned())),
+                _ => bail!("Unknown placement: `{}`", s),
+            },
+        }
+    }
+}
+
+// the empty blocks the appended $weightlists, $animations and $sequences go before, in that
+// order so they're defined before the $sequences that use them
+#[derive(Debug, Clone)]
+pub struct Slots {
+    // the `// merged from <donor>` line of Group and Qci, the .qci starts there
+    banner: Option<BlockId>,
+    weightlists: BlockId,
+    animations: BlockId,
+    sequences: BlockId,
+    // the $pushd folder the slots are in
+    dir: String,
+}
+
+impl QC {
+    // puts a block appended from donor where the placement wants it, returns its id and the
+    // $pushd folder it's in, None if the placement is Kind
+    pub fn place_block(
+        &mut self,
+        command: QCCommand,
+        lines: Vec<String>,
+        donor: &Path,
+    ) -> Result<Option<(BlockId, String)>> {
+        let slots = match &self.slots {
+            Some(slots) => slots.to_owned(),
+            None => match self.make_slots(donor)? {
+                Some(slots) => {
+                    self.slots = Some(slots.to_owned());
+                    slots
+                }
+                None => return Ok(None),
+            },
+        };
+        let slot = match command {
+            QCCommand::Weightlist => slots.weightlists,
+            QCCommand::Animation => slots.animations,
+            QCCommand::Sequence => slots.sequences,
+        };
+        Ok(Some((self.document.insert_before(slot, lines), slots.dir)))
+    }
+
+    fn make_slots(&mut self, donor: &Path) -> Result<Option<Slots>> {
+        let (banner, after, dir) = match &self.placement {
+            Placement::Kind => return Ok(None),
+            Placement::End => (
+                None,
+                self.document.push(Vec::new()),
+                self.end_dir.to_owned(),
+            ),
+            Placement::Group | Placement::Qci => {
+                if self.placement == Placement::Qci {
+                    self.check_qci()?;
+                }
+                let donor = donor.file_name().unwrap_or(donor.as_os_str());
+                let banner = self
+                    .document
+                    .push(vec![format!("// merged from {}", donor.to_string_lossy())]);
+                (Some(banner), banner, self.end_dir.to_owned())
+            }
+            Placement::Anchor(name) => {
+                let anchor = self
+                    .sequences
+                    .get(name)
+                    .map(|seq| (seq.id, &seq.dir))
+                    .or_else(|| self.animations.get(name).map(|anim| (anim.id, &anim.dir)))
+                    .or_else(|| {
+                        self.weightlists
+                            .get(name)
+                            .map(|weight| (weight.id, &weight.dir))
+                    });
+                match anchor {
+                    Some((id, dir)) => (None, id, dir.to_owned()),
+                    None => bail!(
+                        "[placement Error] {} has no block named `{}` to put the merged blocks after",
+                        self.qc_file.display(),
+                        name
+                    ),
+                }
+            }
+        };
+        let weightlists = self.document.insert_after(after, Vec::new());
+        let animations = self.document.insert_after(weightlists, Vec::new());
+        let sequences = self.document.insert_after(animations, Vec::new());
+        Ok(Some(Slots {
+            banner,
+            weightlists,
+            animations,
+            sequences,
+            dir,
+        }))
+    }
+
+    // errors if the qc already $includes the .qci the Qci placement generates, e.g. from an
+    // earlier merge, a new .qci would replace the blocks in it
+    pub fn check_qci(&self) -> Result<()> {
+        let qci_name = self.qci_name().to_lowercase();
+        let included = self.document.lines().iter().any(|line| {
+            let mut words = line.split_whitespace();
+            words.next() == Some("$include")
+                && words.next().is_some_and(|file| {
+                    path::normalize(file.trim_matches('"'))
+                        .file_name()
+                        .is_some_and(|name| name.to_string_lossy().to_lowercase() == qci_name)
+                })
+        });
+        if included {
+            bail!(
+                "[placement Error] {} already $includes {} from an earlier merge, use a different --placement",
+                self.qc_file.display(),
+                self.qci_name()
+            );
+        }
+        Ok(())
+    }
+
+    // the file name of the .qci the Qci placement generates, `<qc name>_merged.qci`
+    pub fn qci_name(&self) -> String {
+        let stem = self.qc_file.file_stem().unwrap_or_default();
+        format!("{}_merged.qci", stem.to_string_lossy())
+    }
+
+    // the lines of the qc file, and the lines of the generated .qci if blocks were appended
+    // with the Qci placement, the qc $includes it where it starts
+    pub fn output_lines(&self) -> (Vec<String>, Option<Vec<String>>) {
+        match (
+            &self.placement,
+            self.slots.as_ref().and_then(|slots| slots.banner),
+        ) {
+            (Placement::Qci, Some(banner)) => {
+                let (mut lines, qci) = self.document.split(banner);
+                lines.push(format!("$include \"{}\"", self.qci_name()));
+                (lines, Some(qci))
+            }
+            _ => (self.document.lines(), None),
+        }
+    }
+}
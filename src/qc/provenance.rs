@@ -0,0 +1,22 @@
+use crate::qc::document::BlockId;
+use crate::qc::QC;
+use std::path::Path;
+
+impl QC {
+    // puts a comment above a block merged from donor if the qc has a provenance label,
+    // e.g. `// replaced from donor.qc lines 7-12, 2021-07-04_13-05-59`
+    // start and end are the block's lines in donor
+    pub fn annotate(&mut self, id: BlockId, action: &str, donor: &Path, start: usize, end: usize) {
+        if let Some(label) = &self.provenance {
+            let comment = format!(
+                "// {} from {} lines {}-{}, {}",
+                action,
+                donor.display(),
+                start + 1,
+                end + 1,
+                label
+            );
+            self.document.annotate(id, vec![comment]);
+        }
+    }
+}
@@ -1,4 +1,6 @@
-use crate::qc::QC;
+use crate::qc::document::BlockId;
+use crate::qc::options::SequenceOptions;
+use crate::qc::{path, rename_header, QCCommand, QC};
 use crate::{dequote, dequote_next, dequote_nth};
 use anyhow::{bail, Result};
 
@@ -7,7 +9,7 @@ use anyhow::{bail, Result};
 pub struct Sequence {
     // the name of the $sequence block
     pub name: String,
-    // the activity of the $sequence
+    // the first activity of the $sequence, all of them and their weights are in options
     pub activity: String,
     // the whole $sequence block, includes `$sequence` to `}`
     pub block: Vec<String>,
@@ -19,20 +21,28 @@ pub struct Sequence {
     pub layer: Vec<String>,
     // the $weightlist it uses
     pub weightlist: String,
+    // the $pushd folder the block is in, relative to the qc file's folder, empty if none
+    pub dir: String,
+    // the options between the first line and `}`, e.g. `fps`, `fadein`, events
+    pub options: SequenceOptions,
     // the index in the block where the start the $sequence is found, add one to get line number
     pub start: usize,
     // the index in the block where the `}` is found, add one to get the line number
     pub end: usize,
+    // the block's lines in the qc's document
+    pub id: BlockId,
 }
 
 impl Sequence {
-    pub fn parse(block: &Vec<String>, idx: usize) -> Result<Sequence> {
-        let mut sequence: Sequence = Default::default();
-
-        sequence.start = idx;
-        sequence.end = idx + block.len() - 1;
+    pub fn parse(block: &[String], idx: usize) -> Result<Sequence> {
+        let mut sequence = Sequence {
+            start: idx,
+            end: idx + block.len() - 1,
+            ..Default::default()
+        };
 
         for line in block {
+            let line = line.trim();
             let mut line_split = line.split_whitespace();
             // gets $sequence name and smd path if it's in sca format
             if line.starts_with("$sequence") {
@@ -41,7 +51,7 @@ impl Sequence {
                     1 => {} // not sca format,
                     2 => {
                         let smd: String = dequote_next!(line_split);
-                        if smd.contains(".smd") {
+                        if path::is_animation_file(&smd) {
                             sequence.smd = smd;
                         } else {
                             sequence.smd = smd;
@@ -50,7 +60,7 @@ impl Sequence {
                     }
                     _ => bail!("[$sequence Error] Weird line at {}: `{}`", idx + 1, line),
                 }
-            } else if line.starts_with("activity") {
+            } else if line.starts_with("activity") && sequence.activity.is_empty() {
                 sequence.activity = dequote_nth!(line_split, 1);
             } else if line.starts_with("addlayer") || line.starts_with("blendlayer") {
                 sequence.layer.push(dequote_nth!(line_split, 1));
@@ -60,7 +70,7 @@ impl Sequence {
                 || line.starts_with('\'') && line.ends_with('\'')
             {
                 let smd: String = dequote!(line);
-                if line.contains(".smd") {
+                if path::is_animation_file(line) {
                     sequence.smd = smd;
                 } else {
                     sequence.smd = smd;
@@ -68,69 +78,239 @@ impl Sequence {
                 }
             }
         }
-        sequence.block = block.clone();
+        sequence.block = block.to_vec();
+        sequence.parse_options();
         Ok(sequence)
     }
+
+    // parses the options from the block, e.g. after its lines were changed
+    pub fn parse_options(&mut self) {
+        self.options = if self.block.len() > 1 {
+            SequenceOptions::parse(&self.block[1..self.block.len() - 1])
+        } else {
+            SequenceOptions::default()
+        };
+    }
+
+    // the block with the option lines written from options,
+    // a single-line $sequence gets braces if it has options
+    pub fn write_block(&self) -> Vec<String> {
+        let lines = self.options.write_lines();
+        let mut block = vec![self.block[0].to_owned()];
+        if self.block.len() > 1 {
+            block.extend(lines);
+            block.push(self.block[self.block.len() - 1].to_owned());
+        } else if !lines.is_empty() {
+            block[0] = format!("{} {{", block[0]);
+            block.extend(lines);
+            block.push("}".to_owned());
+        }
+        block
+    }
+
+    // the names of the sequence's activities, empty if it has none
+    pub fn activities(&self) -> Vec<&str> {
+        let mut activities = self
+            .options
+            .activities
+            .iter()
+            .map(|activity| activity.name.as_str())
+            .collect::<Vec<&str>>();
+        if activities.is_empty() && !self.activity.is_empty() {
+            activities.push(&self.activity);
+        }
+        activities
+    }
+
+    // true if activity is one of the sequence's activities, or neither has one
+    pub fn has_activity<T: AsRef<str>>(&self, activity: T) -> bool {
+        self.activity == activity.as_ref()
+            || self
+                .options
+                .activities
+                .iter()
+                .any(|other| other.name == activity.as_ref())
+    }
+
+    // the smd path relative to the qc file's folder, includes the $pushd folder
+    pub fn smd_path(&self) -> String {
+        if self.dir.is_empty() {
+            self.smd.to_owned()
+        } else {
+            format!("{}/{}", self.dir, self.smd)
+        }
+    }
+}
+
+// how a $sequence from another qc will be merged into the base qc
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergeStatus {
+    // the base qc has a $sequence with the same name, it will be replaced
+    Replace,
+    // the base qc doesn't have the $sequence, it will be appended
+    Append,
+    // the base qc has a $sequence with the same name but it doesn't have the activity,
+    // replace_sequence will fail
+    ActivityMismatch,
+    // the base qc doesn't have the $sequence but has an $animation with the same name
+    NameCollision,
+}
+
+impl MergeStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MergeStatus::Replace => "replace",
+            MergeStatus::Append => "append",
+            MergeStatus::ActivityMismatch => "activity mismatch",
+            MergeStatus::NameCollision => "name collision",
+        }
+    }
 }
 
 impl QC {
-    // replaces self (base qc) with other in qc_data and updates self
+    // checks how other_qc's sequence will be merged into self (base qc)
+    pub fn merge_status<T: AsRef<str>>(&self, other_qc: &Self, seq: T) -> MergeStatus {
+        match self.sequences.get(seq.as_ref()) {
+            Some(base_seq) => {
+                if base_seq.has_activity(&other_qc.sequences[seq.as_ref()].activity) {
+                    MergeStatus::Replace
+                } else {
+                    MergeStatus::ActivityMismatch
+                }
+            }
+            None => {
+                if self.animations.contains_key(seq.as_ref()) {
+                    MergeStatus::NameCollision
+                } else {
+                    MergeStatus::Append
+                }
+            }
+        }
+    }
+
+    // replaces self's (base qc) sequence with other's in the document and updates self
+    // the base's activities and their weights are kept
     // other_qc == "replace with"
-    pub fn replace_sequence<T: AsRef<str>>(
-        &mut self,
-        other_qc: &Self,
-        seq: T,
-        // data of the qc file that will be the output
-        qc_data: &mut Vec<String>,
-    ) -> Result<Sequence> {
-        if other_qc.sequences[seq.as_ref()].activity != self.sequences[seq.as_ref()].activity {
+    pub fn replace_sequence<T: AsRef<str>>(&mut self, other_qc: &Self, seq: T) -> Result<Sequence> {
+        let (base_seq, other_seq) = (
+            &self.sequences[seq.as_ref()],
+            &other_qc.sequences[seq.as_ref()],
+        );
+        if !base_seq.has_activity(&other_seq.activity) {
             bail!(
                 "[$sequence Error] Activities don't match: `{}` != `{}`",
-                other_qc.sequences[seq.as_ref()].activity,
-                self.sequences[seq.as_ref()].activity,
+                other_seq.activity,
+                base_seq.activity,
             );
         }
-        // deletes the other sequence in qc_data
-        for _ in 0..=self.sequences[seq.as_ref()].end - self.sequences[seq.as_ref()].start {
-            qc_data.remove(self.sequences[seq.as_ref()].start);
-        }
-        // inserts self sequence where other_seq was
-        other_qc.sequences[seq.as_ref()]
-            .block
-            .iter()
-            .rev()
-            .for_each(|line| qc_data.insert(self.sequences[seq.as_ref()].start, line.to_owned()));
-
-        // updates self qc to have correct line numbers
-        *self = Self::parse(&self.qc_file.clone(), qc_data.clone().into_iter())?;
+        // the block is only rewritten if the activities are different
+        let block = if other_seq.options.activities == base_seq.options.activities {
+            other_seq.block.clone()
+        } else {
+            let mut other_seq = other_seq.clone();
+            other_seq.options.activities = base_seq.options.activities.clone();
+            other_seq.write_block()
+        };
+        // other's sequence goes where self's was, in the same $pushd folder
+        let mut sequence = Sequence::parse(&block, other_seq.start)?;
+        sequence.id = base_seq.id;
+        sequence.dir = base_seq.dir.to_owned();
+        self.classify_sequence(&mut sequence);
+        self.document.replace(sequence.id, block);
+        let (start, end) = (other_seq.start, other_seq.end);
+        self.annotate(sequence.id, "replaced", &other_qc.qc_file, start, end);
+        self.sequences.insert(sequence.name.to_owned(), sequence);
         Ok(other_qc.sequences[seq.as_ref()].clone())
     }
 
-    // appends sequence and updates
-    pub fn append_sequence<T: AsRef<str>>(
-        &mut self,
-        other_qc: &Self,
-        seq: T,
-        qc_data: &mut Vec<String>,
-    ) -> Result<Sequence> {
-        other_qc.sequences[seq.as_ref()]
-            .block
-            .iter()
-            .rev()
-            .for_each(|line| {
-                qc_data.insert(
-                    self.sequences
-                        .iter()
-                        .max_by(|(_, x), (_, y)| x.end.cmp(&y.end))
-                        .unwrap()
-                        .1
-                        .end
-                        + 1,
-                    line.to_owned(),
-                )
-            });
-
-        *self = Self::parse(&self.qc_file.clone(), qc_data.clone().into_iter())?;
+    // renames a $sequence of self (donor qc) so it can be appended to base_qc as well as
+    // the sequence it would replace, it gets that sequence's activities so the game picks one
+    // of them at random by weight
+    // returns the new name, `name_alt`, `name_alt2`, ...
+    pub fn make_alternate<T: AsRef<str>>(&mut self, base_qc: &Self, seq: T) -> Result<String> {
+        let seq = seq.as_ref();
+        let taken = |name: &str| {
+            [self, base_qc]
+                .iter()
+                .any(|qc| qc.sequences.contains_key(name) || qc.animations.contains_key(name))
+        };
+        let name = (1..)
+            .map(|i| match i {
+                1 => format!("{}_alt", seq),
+                i => format!("{}_alt{}", seq, i),
+            })
+            .find(|name| !taken(name))
+            .unwrap();
+
+        let base_seq = match base_qc.sequences.get(seq) {
+            Some(base_seq) => base_seq,
+            None => bail!("[$sequence Error] The base doesn't have `{}`", seq),
+        };
+        // the alternate takes the donor sequence's place, so the order stays the same
+        let (index, _, mut sequence) = match self.sequences.shift_remove_full(seq) {
+            Some(sequence) => sequence,
+            None => bail!(
+                "[$sequence Error] `{}` isn't in {}",
+                seq,
+                self.qc_file.display()
+            ),
+        };
+        sequence.block[0] = rename_header(&sequence.block[0], &name);
+        sequence.name = name.to_owned();
+        sequence.activity = base_seq.activity.to_owned();
+        sequence.options.activities = base_seq.options.activities.clone();
+        sequence.block = sequence.write_block();
+        self.sequences
+            .shift_insert(index, name.to_owned(), sequence);
+        Ok(name)
+    }
+
+    // writes the block of a $sequence to the document after its options were changed,
+    // see Sequence::write_block
+    pub fn write_sequence<T: AsRef<str>>(&mut self, seq: T) -> Result<()> {
+        let old = &self.sequences[seq.as_ref()];
+        let block = old.write_block();
+        let mut sequence = Sequence::parse(&block, old.start)?;
+        sequence.id = old.id;
+        sequence.dir = old.dir.to_owned();
+        sequence.uses_animation = old.uses_animation;
+        self.document.replace(sequence.id, block);
+        self.sequences.insert(seq.as_ref().to_owned(), sequence);
+        Ok(())
+    }
+
+    // sets the `fps` of a $sequence in the document
+    pub fn set_fps<T: AsRef<str>>(&mut self, seq: T, fps: f64) -> Result<()> {
+        if let Some(sequence) = self.sequences.get_mut(seq.as_ref()) {
+            sequence.options.fps = Some(fps);
+        }
+        self.write_sequence(seq)
+    }
+
+    // appends other's sequence where the placement wants it, after the last $sequence by
+    // default, and updates self
+    pub fn append_sequence<T: AsRef<str>>(&mut self, other_qc: &Self, seq: T) -> Result<Sequence> {
+        let mut sequence = other_qc.sequences[seq.as_ref()].clone();
+        let block = sequence.block.clone();
+        if let Some((id, dir)) = self.place_block(QCCommand::Sequence, block, &other_qc.qc_file)? {
+            sequence.id = id;
+            sequence.dir = dir;
+        } else {
+            let last = match self.sequences.last() {
+                Some((_, last)) => last,
+                None => bail!(
+                    "[$sequence Error] {} has no $sequence to append `{}` after",
+                    self.qc_file.display(),
+                    sequence.name
+                ),
+            };
+            sequence.id = self.document.insert_after(last.id, sequence.block.clone());
+            sequence.dir = last.dir.to_owned();
+        }
+        self.classify_sequence(&mut sequence);
+        let (start, end) = (sequence.start, sequence.end);
+        self.annotate(sequence.id, "appended", &other_qc.qc_file, start, end);
+        self.sequences.insert(sequence.name.to_owned(), sequence);
         Ok(other_qc.sequences[seq.as_ref()].clone())
     }
 }
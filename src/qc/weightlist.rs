@@ -1,5 +1,7 @@
 use crate::dequote_nth;
-use crate::qc::QC;
+use crate::qc::document::BlockId;
+use crate::qc::{indent, QCCommand, QC};
+use crate::smd::retarget::BoneMap;
 use anyhow::{bail, Result};
 
 // for $weightlist
@@ -9,22 +11,29 @@ pub struct Weightlist {
     pub name: String,
     // the whole $weightlist block, includes `$weightlist` to `}`
     pub block: Vec<String>,
+    // the bones and their weights, in order
+    pub weights: Vec<(String, f64)>,
+    // the $pushd folder the block is in, relative to the qc file's folder, empty if none
+    pub dir: String,
     // the index in the block where the start the $weightlist is found, add one to get line number
     pub start: usize,
     // the index in the block where the `}` is found, add one to get the line number
     pub end: usize,
+    // the block's lines in the qc's document
+    pub id: BlockId,
 }
 
 impl Weightlist {
-    pub fn parse(block: &Vec<String>, idx: usize) -> Result<Weightlist> {
-        let mut weightlist: Weightlist = Default::default();
-
-        weightlist.start = idx;
-        weightlist.end = idx + block.len() - 1;
+    pub fn parse(block: &[String], idx: usize) -> Result<Weightlist> {
+        let mut weightlist = Weightlist {
+            start: idx,
+            end: idx + block.len() - 1,
+            ..Default::default()
+        };
 
         let mut line_split = block[0].split_whitespace();
         // gets $weightlist name
-        if block[0].starts_with("$weightlist") {
+        if block[0].trim_start().starts_with("$weightlist") {
             match line_split.clone().count() {
                 3 => weightlist.name = dequote_nth!(line_split, 1),
                 _ => bail!(
@@ -35,54 +44,225 @@ impl Weightlist {
             }
         }
 
-        weightlist.block = block.clone();
+        // `"bone" weight` lines, the weight can be quoted, anything else is ignored
+        for (i, line) in block.iter().enumerate().skip(1) {
+            let option = line.split("//").next().unwrap_or_default();
+            let mut line_split = option.split_whitespace();
+            if line_split.clone().count() != 2 {
+                continue;
+            }
+            let bone: String = dequote_nth!(line_split, 0);
+            match line_split
+                .next()
+                .map(|weight| weight.trim_matches('"').parse())
+            {
+                Some(Ok(weight)) => weightlist.weights.push((bone, weight)),
+                _ => bail!(
+                    "[$weightlist Error] Weird weight at {}: `{}`",
+                    idx + i + 1,
+                    line
+                ),
+            }
+        }
+
+        weightlist.block = block.to_vec();
         Ok(weightlist)
     }
+
+    // the weight of a bone, bones are compared ignoring case like studiomdl does
+    pub fn weight<T: AsRef<str>>(&self, bone: T) -> Option<f64> {
+        self.weights
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(bone.as_ref()))
+            .map(|(_, weight)| *weight)
+    }
+
+    // the bones whose weights are different in other, self's bones first,
+    // empty if both lists have the same weights
+    pub fn diff(&self, other: &Weightlist) -> Vec<WeightDiff> {
+        self.weights
+            .iter()
+            .map(|(bone, _)| bone)
+            .chain(
+                other
+                    .weights
+                    .iter()
+                    .map(|(bone, _)| bone)
+                    .filter(|bone| self.weight(bone).is_none()),
+            )
+            .map(|bone| WeightDiff {
+                bone: bone.to_owned(),
+                weight: self.weight(bone),
+                other_weight: other.weight(bone),
+            })
+            .filter(|diff| diff.weight != diff.other_weight)
+            .collect()
+    }
+}
+
+// a bone whose weight is different in two $weightlists, None if a list doesn't have it
+#[derive(Debug, Clone, PartialEq)]
+pub struct WeightDiff {
+    pub bone: String,
+    pub weight: Option<f64>,
+    pub other_weight: Option<f64>,
 }
 
 impl QC {
+    // appends other's weightlist where the placement wants it, by default before the first
+    // $weightlist, $sequence or $animation, and updates self
     pub fn append_weightlist<T: AsRef<str>>(
         &mut self,
         other_qc: &Self,
         weight: T,
-        qc_data: &mut Vec<String>,
     ) -> Result<String> {
-        let weightlist_rev = other_qc.weightlists[weight.as_ref()].block.iter().rev();
-        // if qc has at least one $weightlist, append weightlist next to it
-        if !self.weightlists.is_empty() {
-            weightlist_rev.for_each(|line| {
-                qc_data.insert(
-                    self.weightlists.iter().next().unwrap().1.start,
-                    line.to_owned(),
-                )
-            });
-        } else if !self.sequences.is_empty() {
-            weightlist_rev.for_each(|line| {
-                qc_data.insert(
-                    self.sequences
-                        .iter()
-                        .min_by(|(_, x), (_, y)| x.start.cmp(&y.start))
-                        .unwrap()
-                        .1
-                        .start,
-                    line.to_owned(),
-                )
-            });
-        } else if !self.animations.is_empty() {
-            weightlist_rev.for_each(|line| {
-                qc_data.insert(
-                    self.animations
-                        .iter()
-                        .min_by(|(_, x), (_, y)| x.start.cmp(&y.start))
-                        .unwrap()
-                        .1
-                        .start,
-                    line.to_owned(),
-                )
-            });
+        let mut weightlist = other_qc.weightlists[weight.as_ref()].clone();
+        let block = weightlist.block.clone();
+        let (start, end) = (weightlist.start, weightlist.end);
+        if let Some((id, dir)) =
+            self.place_block(QCCommand::Weightlist, block.clone(), &other_qc.qc_file)?
+        {
+            weightlist.id = id;
+            weightlist.dir = dir;
+            self.annotate(id, "appended", &other_qc.qc_file, start, end);
+            self.weightlists
+                .insert(weightlist.name.to_owned(), weightlist);
+            return Ok(weight.as_ref().to_owned());
         }
-
-        *self = Self::parse(&self.qc_file.clone(), qc_data.clone().into_iter())?;
+        let first = if let Some((_, first)) = self.weightlists.first() {
+            (first.id, first.dir.to_owned())
+        } else if let Some((_, first)) = self.sequences.first() {
+            (first.id, first.dir.to_owned())
+        } else if let Some((_, first)) = self.animations.first() {
+            (first.id, first.dir.to_owned())
+        } else {
+            bail!(
+                "[$weightlist Error] {} has no block to put `{}` next to",
+                self.qc_file.display(),
+                weightlist.name
+            );
+        };
+        weightlist.id = self.document.insert_before(first.0, block);
+        weightlist.dir = first.1;
+        self.annotate(weightlist.id, "appended", &other_qc.qc_file, start, end);
+        self.weightlists
+            .shift_insert(0, weightlist.name.to_owned(), weightlist);
         Ok(weight.as_ref().to_owned())
     }
+
+    // renames the bones of a $weightlist with the bone map, e.g. after copying it from a
+    // qc with a different skeleton
+    pub fn rename_weightlist_bones<T: AsRef<str>>(
+        &mut self,
+        weight: T,
+        map: &BoneMap,
+    ) -> Result<()> {
+        let old = &self.weightlists[weight.as_ref()];
+        let mut block = old.block.clone();
+        let lines = block.len() - 1;
+        for line in block[1..lines].iter_mut() {
+            let mut line_split = line.split_whitespace();
+            if line_split.clone().count() < 2 {
+                continue;
+            }
+            let bone: String = dequote_nth!(line_split, 0);
+            if let Some(to) = map.get(&bone) {
+                *line = format!(
+                    "{}\"{}\" {}",
+                    indent(line),
+                    to,
+                    line_split.collect::<Vec<&str>>().join(" ")
+                );
+            }
+        }
+
+        let mut weightlist = Weightlist::parse(&block, old.start)?;
+        weightlist.id = old.id;
+        weightlist.dir = old.dir.to_owned();
+        self.document.replace(weightlist.id, block);
+        self.weightlists
+            .insert(weight.as_ref().to_owned(), weightlist);
+        Ok(())
+    }
+
+    // the $weightlists used by the sequences and the $animations they use, sorted
+    pub fn used_weightlists(&self, names: &[String]) -> Vec<String> {
+        let mut weightlists = Vec::new();
+        for seq in names.iter().filter_map(|name| self.sequences.get(name)) {
+            weightlists.push(seq.weightlist.to_owned());
+            if seq.uses_animation {
+                if let Some(anim) = self.animations.get(&seq.smd) {
+                    weightlists.push(anim.weightlist.to_owned());
+                }
+            }
+        }
+        weightlists.retain(|weightlist| !weightlist.is_empty());
+        weightlists.sort();
+        weightlists.dedup();
+        weightlists
+    }
+
+    // renames a $weightlist and the references to it in the blocks, without changing the qc
+    // file, e.g. so a donor's list can be appended next to a different base list with the
+    // same name
+    pub fn rename_weightlist<T: AsRef<str>>(&mut self, weight: T, new_name: T) -> Result<()> {
+        let (weight, new_name) = (weight.as_ref(), new_name.as_ref());
+        let (index, _, mut weightlist) = match self.weightlists.shift_remove_full(weight) {
+            Some(weightlist) => weightlist,
+            None => bail!(
+                "[$weightlist Error] `{}` isn't in {}",
+                weight,
+                self.qc_file.display()
+            ),
+        };
+        // Weightlist::parse only allows `$weightlist name {`
+        weightlist.block[0] = format!(
+            "{}$weightlist \"{}\" {{",
+            indent(&weightlist.block[0]),
+            new_name
+        );
+        weightlist.name = new_name.to_owned();
+        self.weightlists
+            .shift_insert(index, new_name.to_owned(), weightlist);
+
+        let rename = |block: &mut Vec<String>| {
+            for line in block.iter_mut() {
+                let mut line_split = line.split_whitespace();
+                if line.trim_start().starts_with("weightlist")
+                    && dequote_nth!(line_split, 1) == weight
+                {
+                    *line = format!("{}weightlist \"{}\"", indent(line), new_name);
+                }
+            }
+        };
+        for seq in self.sequences.values_mut() {
+            if seq.weightlist == weight {
+                rename(&mut seq.block);
+                seq.weightlist = new_name.to_owned();
+                seq.parse_options();
+            }
+        }
+        for anim in self.animations.values_mut() {
+            if anim.weightlist == weight {
+                rename(&mut anim.block);
+                anim.weightlist = new_name.to_owned();
+            }
+        }
+        Ok(())
+    }
+
+    // replaces self's (base qc) $weightlist with other_qc's one with the same name
+    pub fn replace_weightlist<T: AsRef<str>>(&mut self, other_qc: &Self, weight: T) -> Result<()> {
+        let base_weight = &self.weightlists[weight.as_ref()];
+        let mut weightlist = other_qc.weightlists[weight.as_ref()].clone();
+        weightlist.id = base_weight.id;
+        weightlist.dir = base_weight.dir.to_owned();
+        self.document
+            .replace(weightlist.id, weightlist.block.clone());
+        let (start, end) = (weightlist.start, weightlist.end);
+        self.annotate(weightlist.id, "replaced", &other_qc.qc_file, start, end);
+        self.weightlists
+            .insert(weight.as_ref().to_owned(), weightlist);
+        Ok(())
+    }
 }
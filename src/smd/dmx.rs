@@ -0,0 +1,91 @@
+use anyhow::{bail, Result};
+
+// a token of a keyvalues2 dmx, strings are unquoted
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    String(String),
+    Open,
+    Close,
+}
+
+// gets the bone names of a keyvalues2 text dmx, in the order they're defined
+// bones are the `DmeJoint` elements, e.g.
+// "DmeJoint"
+// {
+//     "name" "string" "bip_pelvis"
+//     ...
+// }
+pub fn bone_names<T: AsRef<str>>(data: T) -> Result<Vec<String>> {
+    let data = data.as_ref();
+    // e.g. `<!-- dmx encoding keyvalues2 1 format model 18 -->`
+    match data.lines().next() {
+        Some(header) if header.contains("encoding keyvalues2") => {}
+        Some(header) if header.contains("encoding binary") => {
+            bail!("[dmx Error] Binary dmx files aren't supported, save it as keyvalues2 text")
+        }
+        _ => bail!("[dmx Error] Missing the keyvalues2 header"),
+    }
+
+    let tokens = tokenize(data.lines().skip(1))?;
+    let mut bones: Vec<String> = Vec::new();
+    // the element types of the `{` blocks the current token is in
+    let mut elements: Vec<&str> = Vec::new();
+    let mut last_string = "";
+    for (i, token) in tokens.iter().enumerate() {
+        match token {
+            // an element's type is the string right before its `{`
+            Token::Open => elements.push(last_string),
+            Token::Close => {
+                if elements.pop().is_none() {
+                    bail!("[dmx Error] Unexpected `}}`");
+                }
+            }
+            Token::String(string) => {
+                last_string = string;
+                if string == "name" && elements.last() == Some(&"DmeJoint") {
+                    if let (Some(Token::String(kind)), Some(Token::String(name))) =
+                        (tokens.get(i + 1), tokens.get(i + 2))
+                    {
+                        if kind == "string" && !bones.contains(name) {
+                            bones.push(name.to_owned());
+                        }
+                    }
+                }
+            }
+        }
+    }
+    if !elements.is_empty() {
+        bail!("[dmx Error] Missing `}}`");
+    }
+    Ok(bones)
+}
+
+// splits keyvalues2 text into quoted strings and `{`/`}`, arrays and comments are skipped
+fn tokenize<'a, I: Iterator<Item = &'a str>>(lines: I) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    for (idx, line) in lines.enumerate() {
+        let mut chars = line.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' => {
+                    let mut string = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('"') => break,
+                            Some('\\') => string.extend(chars.next()),
+                            Some(c) => string.push(c),
+                            // the header is skipped, so the line number is idx + 2
+                            None => bail!("[dmx Error] Unclosed string at {}", idx + 2),
+                        }
+                    }
+                    tokens.push(Token::String(string));
+                }
+                '{' => tokens.push(Token::Open),
+                '}' => tokens.push(Token::Close),
+                '/' if chars.clone().next() == Some('/') => break,
+                _ => {}
+            }
+        }
+    }
+    Ok(tokens)
+}
@@ -0,0 +1,103 @@
+use crate::smd::{Frame, Smd};
+use anyhow::{bail, Context, Result};
+use std::collections::BTreeMap;
+
+impl Smd {
+    // makes every frame from the first `time` to the last have every bone,
+    // bones missing from a frame keep their last transform, like studiomdl does
+    // the frames are renumbered from 0
+    pub fn fill_frames(&mut self) {
+        let mut frames = self.frames.clone();
+        frames.sort_by_key(|frame| frame.time);
+        let first = match frames.first() {
+            Some(frame) => frame.time,
+            None => return,
+        };
+
+        // the first transform of each bone is used for the frames before it's keyed
+        let mut bones = BTreeMap::new();
+        for bone in frames.iter().flat_map(|frame| frame.bones.iter()).rev() {
+            bones.insert(bone.index, bone.clone());
+        }
+        let mut keyed = frames.into_iter().peekable();
+        self.frames = (0..self.frame_count() as i32)
+            .map(|time| {
+                while let Some(frame) = keyed.next_if(|frame| frame.time - first <= time) {
+                    for bone in frame.bones {
+                        bones.insert(bone.index, bone);
+                    }
+                }
+                Frame {
+                    time,
+                    bones: bones.values().cloned().collect(),
+                }
+            })
+            .collect();
+    }
+
+    // keeps the frames from start to end, both included, counting from the first frame
+    pub fn trim(&mut self, start: usize, end: usize) -> Result<()> {
+        let frames = self.frame_count();
+        if start > end || end >= frames {
+            bail!(
+                "[smd Error] Can't trim frames {} to {}, it has frames 0 to {}",
+                start,
+                end,
+                frames as i64 - 1
+            );
+        }
+        self.fill_frames();
+        self.frames = self.frames.drain(start..=end).collect();
+        for (time, frame) in self.frames.iter_mut().enumerate() {
+            frame.time = time as i32;
+        }
+        Ok(())
+    }
+
+    // plays the animation backwards
+    pub fn reverse(&mut self) {
+        self.fill_frames();
+        self.frames.reverse();
+        for (time, frame) in self.frames.iter_mut().enumerate() {
+            frame.time = time as i32;
+        }
+    }
+
+    // adds the frames of other after the last frame, bones are matched by name
+    // drop_first skips other's first frame, for when it's the same as the last frame
+    pub fn concat(&mut self, other: &Smd, drop_first: bool) -> Result<()> {
+        let mut other = other.clone();
+        other.fill_frames();
+        // other's bone indices to self's
+        let mut indices = BTreeMap::new();
+        for node in &other.nodes {
+            let index = self
+                .node(&node.name)
+                .with_context(|| {
+                    format!("[smd Error] `{}` isn't a bone of the first smd", node.name)
+                })?
+                .index;
+            indices.insert(node.index, index);
+        }
+
+        self.fill_frames();
+        let offset = self.frames.len() as i32;
+        let skip = if drop_first { 1 } else { 0 };
+        for (time, mut frame) in other.frames.into_iter().skip(skip).enumerate() {
+            frame.time = offset + time as i32;
+            for bone in frame.bones.iter_mut() {
+                bone.index = *indices.get(&bone.index).with_context(|| {
+                    format!(
+                        "[smd Error] Bone {} of the second smd's frame {} isn't in its nodes",
+                        bone.index, time
+                    )
+                })?;
+            }
+            frame.bones.sort_by_key(|bone| bone.index);
+            self.frames.push(frame);
+        }
+        // bones other doesn't have keep their last transform
+        self.fill_frames();
+        Ok(())
+    }
+}
@@ -0,0 +1,323 @@
+pub mod dmx;
+pub mod edit;
+pub mod resample;
+pub mod retarget;
+
+use crate::qc::{path, QC};
+use anyhow::{bail, Context, Result};
+use retarget::BoneMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+// a bone from the `nodes` section of an smd
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Node {
+    // the bone's index, `skeleton` frames refer to bones by it
+    pub index: i32,
+    // the bone's name
+    pub name: String,
+    // the index of the parent bone, -1 if it's a root bone
+    pub parent: i32,
+}
+
+// the transform of a bone in one frame of the `skeleton` section
+#[derive(Debug, Clone, PartialEq)]
+pub struct BoneFrame {
+    // the index of the bone in `nodes`
+    pub index: i32,
+    // the position relative to the parent bone
+    pub position: [f64; 3],
+    // the rotation relative to the parent bone, euler angles in radians
+    pub rotation: [f64; 3],
+}
+
+// a `time` block of the `skeleton` section
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    // the frame number
+    pub time: i32,
+    // the bones that have a transform in this frame
+    pub bones: Vec<BoneFrame>,
+}
+
+// an smd file, sections other than `nodes` and `skeleton` are kept as lines
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Smd {
+    // the lines before `nodes`, e.g. `version 1` and comments
+    pub header: Vec<String>,
+    // the bones
+    pub nodes: Vec<Node>,
+    // the frames of the `skeleton` section
+    pub frames: Vec<Frame>,
+    // the lines of the `triangles` section without `triangles` and `end`, empty for animations
+    pub triangles: Vec<String>,
+    // any other sections, e.g. `vertexanimation`, includes their first line and `end`
+    pub other: Vec<String>,
+}
+
+impl Smd {
+    pub fn new<P: AsRef<Path>>(file: P) -> Result<Smd> {
+        let data = fs::read_to_string(&file)
+            .with_context(|| format!("Failed to read: {}", file.as_ref().display()))?;
+        Self::parse(data).with_context(|| format!("Failed to parse: {}", file.as_ref().display()))
+    }
+
+    // parses an smd and returns an smd struct
+    pub fn parse<T: AsRef<str>>(data: T) -> Result<Smd> {
+        let mut smd = Smd::default();
+        let mut lines = data.as_ref().lines().enumerate();
+
+        while let Some((idx, line)) = lines.next() {
+            let line = line.trim();
+            match line {
+                "nodes" => {
+                    for (idx, line) in section(&mut lines)? {
+                        smd.nodes.push(parse_node(line, idx)?);
+                    }
+                }
+                "skeleton" => {
+                    for (idx, line) in section(&mut lines)? {
+                        let line = strip_comment(line);
+                        if let Some(time) = line.strip_prefix("time") {
+                            smd.frames.push(Frame {
+                                time: time.trim().parse().with_context(|| {
+                                    format!("[smd Error] Weird time at {}: `{}`", idx + 1, line)
+                                })?,
+                                bones: Vec::new(),
+                            });
+                        } else if !line.is_empty() {
+                            let bone = parse_bone_frame(line, idx)?;
+                            match smd.frames.last_mut() {
+                                Some(frame) => frame.bones.push(bone),
+                                None => bail!(
+                                    "[smd Error] Bone before the first time at {}: `{}`",
+                                    idx + 1,
+                                    line
+                                ),
+                            }
+                        }
+                    }
+                }
+                "triangles" => {
+                    smd.triangles = section(&mut lines)?
+                        .into_iter()
+                        .map(|(_, line)| line.to_owned())
+                        .collect();
+                }
+                _ if smd.nodes.is_empty() && smd.frames.is_empty() => {
+                    smd.header.push(line.to_owned())
+                }
+                _ if line.is_empty() || line.starts_with("//") || line.starts_with('#') => {}
+                _ => {
+                    smd.other.push(line.to_owned());
+                    for (_, line) in section(&mut lines)? {
+                        smd.other.push(line.to_owned());
+                    }
+                    smd.other.push("end".to_owned());
+                }
+            }
+            if smd.nodes.is_empty() && line == "nodes" {
+                bail!("[smd Error] Empty `nodes` section at {}", idx + 1);
+            }
+        }
+        if smd.nodes.is_empty() {
+            bail!("[smd Error] Missing `nodes` section");
+        }
+        Ok(smd)
+    }
+
+    // writes the smd to a file
+    pub fn write<P: AsRef<Path>>(&self, file: P) -> Result<()> {
+        fs::write(&file, self.to_string())
+            .with_context(|| format!("Failed to write: {}", file.as_ref().display()))
+    }
+
+    // gets the node with the name, ignoring case like studiomdl does
+    pub fn node<T: AsRef<str>>(&self, name: T) -> Option<&Node> {
+        self.nodes
+            .iter()
+            .find(|node| node.name.eq_ignore_ascii_case(name.as_ref()))
+    }
+}
+
+impl fmt::Display for Smd {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for line in &self.header {
+            writeln!(f, "{}", line)?;
+        }
+        writeln!(f, "nodes")?;
+        for node in &self.nodes {
+            writeln!(f, "  {} \"{}\" {}", node.index, node.name, node.parent)?;
+        }
+        writeln!(f, "end")?;
+        writeln!(f, "skeleton")?;
+        for frame in &self.frames {
+            writeln!(f, "  time {}", frame.time)?;
+            for bone in &frame.bones {
+                let [x, y, z] = bone.position;
+                let [rx, ry, rz] = bone.rotation;
+                writeln!(
+                    f,
+                    "    {} {:.6} {:.6} {:.6} {:.6} {:.6} {:.6}",
+                    bone.index, x, y, z, rx, ry, rz
+                )?;
+            }
+        }
+        writeln!(f, "end")?;
+        if !self.triangles.is_empty() {
+            writeln!(f, "triangles")?;
+            for line in &self.triangles {
+                writeln!(f, "{}", line)?;
+            }
+            writeln!(f, "end")?;
+        }
+        for line in &self.other {
+            writeln!(f, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+// parses the `nodes` section of an smd, e.g. `  1 "bip_collar_L" 0`
+// stops at the end of the section, so it's cheaper than Smd::parse
+pub fn parse_nodes<T: AsRef<str>>(data: T) -> Result<Vec<Node>> {
+    let mut lines = data.as_ref().lines().enumerate();
+    if !lines.any(|(_, line)| line.trim() == "nodes") {
+        bail!("[smd Error] Missing `nodes` section");
+    }
+    section(&mut lines)?
+        .into_iter()
+        .map(|(idx, line)| parse_node(line, idx))
+        .collect()
+}
+
+// gets the trimmed lines of a section up to its `end`
+fn section<'a, I: Iterator<Item = (usize, &'a str)>>(
+    lines: &mut I,
+) -> Result<Vec<(usize, &'a str)>> {
+    let mut section = Vec::new();
+    for (idx, line) in lines {
+        let line = line.trim();
+        if line == "end" {
+            return Ok(section);
+        }
+        section.push((idx, line));
+    }
+    bail!("[smd Error] Section has no `end`")
+}
+
+// removes `#` and `//` comments, e.g. Crowbar adds them after bone transforms
+fn strip_comment(line: &str) -> &str {
+    let end = [line.find('#'), line.find("//")]
+        .iter()
+        .flatten()
+        .min()
+        .copied()
+        .unwrap_or(line.len());
+    line[..end].trim()
+}
+
+// parses a line of the `nodes` section, the name is quoted and can have spaces
+fn parse_node(line: &str, idx: usize) -> Result<Node> {
+    if let (Some(open), Some(close)) = (line.find('"'), line.rfind('"')) {
+        if let (true, Ok(index), Ok(parent)) = (
+            open < close,
+            line[..open].trim().parse::<i32>(),
+            strip_comment(&line[close + 1..]).parse::<i32>(),
+        ) {
+            return Ok(Node {
+                index,
+                name: line[open + 1..close].to_owned(),
+                parent,
+            });
+        }
+    }
+    bail!("[smd Error] Weird node at {}: `{}`", idx + 1, line)
+}
+
+// parses a bone line of the `skeleton` section, e.g. `0 0.0 1.0 0.0 0.0 0.0 1.570796`
+fn parse_bone_frame(line: &str, idx: usize) -> Result<BoneFrame> {
+    let weird = || format!("[smd Error] Weird bone at {}: `{}`", idx + 1, line);
+    let mut line_split = line.split_whitespace();
+    let index = line_split
+        .next()
+        .and_then(|index| index.parse::<i32>().ok())
+        .with_context(weird)?;
+    let values = line_split
+        .map(|value| value.parse::<f64>())
+        .collect::<std::result::Result<Vec<f64>, _>>()
+        .ok()
+        .filter(|values| values.len() == 6)
+        .with_context(weird)?;
+    Ok(BoneFrame {
+        index,
+        position: [values[0], values[1], values[2]],
+        rotation: [values[3], values[4], values[5]],
+    })
+}
+
+// true if the file is a dmx, going by its extension
+pub fn is_dmx<P: AsRef<Path>>(file: P) -> bool {
+    file.as_ref()
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("dmx"))
+}
+
+// gets the bone names of an smd or a keyvalues2 text dmx, in the order they're defined
+pub fn bone_names<P: AsRef<Path>>(file: P) -> Result<Vec<String>> {
+    let data =
+        fs::read(&file).with_context(|| format!("Failed to read: {}", file.as_ref().display()))?;
+    let data = String::from_utf8_lossy(&data);
+    if is_dmx(&file) {
+        dmx::bone_names(data)
+    } else {
+        Ok(parse_nodes(data)?
+            .into_iter()
+            .map(|node| node.name)
+            .collect())
+    }
+    .with_context(|| format!("Failed to read the bones of: {}", file.as_ref().display()))
+}
+
+// gets the bones of the file that aren't in skeleton after renaming them with the bone map,
+// bone names are compared ignoring case like studiomdl does
+pub fn missing_bones<P: AsRef<Path>>(
+    file: P,
+    skeleton: &[String],
+    map: &BoneMap,
+) -> Result<Vec<String>> {
+    Ok(bone_names(file)?
+        .into_iter()
+        .filter(|bone| {
+            !skeleton
+                .iter()
+                .any(|other| other.eq_ignore_ascii_case(map.rename(bone)))
+        })
+        .collect())
+}
+
+// the skeleton of a qc, its $definebone bones,
+// or the bones of all of its smds and dmxs in the order they're found if it has none
+pub fn skeleton(qc: &QC) -> Vec<String> {
+    if !qc.bones.is_empty() {
+        return qc.bones.clone();
+    }
+    let mut qc_dir = qc.qc_file.clone();
+    qc_dir.pop();
+    let mut smds = qc.get_smds();
+    smds.sort();
+
+    let mut skeleton: Vec<String> = Vec::new();
+    for smd in smds {
+        for bone in bone_names(path::resolve(&qc_dir, smd)).unwrap_or_default() {
+            if !skeleton
+                .iter()
+                .any(|other| other.eq_ignore_ascii_case(&bone))
+            {
+                skeleton.push(bone);
+            }
+        }
+    }
+    skeleton
+}
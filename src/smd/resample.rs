@@ -0,0 +1,172 @@
+use crate::smd::{BoneFrame, Frame, Smd};
+use anyhow::{bail, Result};
+use std::collections::BTreeMap;
+
+// the fps studiomdl uses when a $sequence doesn't have `fps`
+pub const DEFAULT_FPS: f64 = 30.0;
+
+// a rotation as a quaternion, `[x, y, z, w]`
+type Quaternion = [f64; 4];
+
+impl Smd {
+    // the number of frames, from the first `time` to the last
+    pub fn frame_count(&self) -> usize {
+        match (
+            self.frames.iter().map(|frame| frame.time).min(),
+            self.frames.iter().map(|frame| frame.time).max(),
+        ) {
+            (Some(first), Some(last)) => (last - first) as usize + 1,
+            _ => 0,
+        }
+    }
+
+    // changes the number of frames, the animation keeps its length in frames so it plays
+    // at the same speed once the fps is changed by the same ratio
+    // positions are interpolated linearly and rotations with slerp
+    // bones and frames missing from the smd keep their last transform first, see fill_frames
+    pub fn resample(&mut self, frames: usize) -> Result<()> {
+        if frames == 0 {
+            bail!("[smd Error] Can't resample to 0 frames");
+        }
+        let old_frames = self.frame_count();
+        if old_frames == 0 {
+            bail!("[smd Error] `skeleton` has no frames");
+        }
+        self.fill_frames();
+
+        // the transform of each bone, by time
+        let mut tracks: BTreeMap<i32, BTreeMap<i32, &BoneFrame>> = BTreeMap::new();
+        for frame in &self.frames {
+            for bone in &frame.bones {
+                tracks
+                    .entry(bone.index)
+                    .or_default()
+                    .insert(frame.time, bone);
+            }
+        }
+
+        let new_frames = (0..frames)
+            .map(|frame| {
+                // where the frame is on the old timeline
+                let time = if frames == 1 {
+                    0.0
+                } else {
+                    frame as f64 * (old_frames - 1) as f64 / (frames - 1) as f64
+                };
+                Frame {
+                    time: frame as i32,
+                    bones: tracks.values().map(|track| sample(track, time)).collect(),
+                }
+            })
+            .collect();
+        self.frames = new_frames;
+        Ok(())
+    }
+
+    // resamples the animation from one fps to another, it plays at the same speed at new_fps
+    pub fn conform_fps(&mut self, fps: f64, new_fps: f64) -> Result<()> {
+        if fps <= 0.0 || new_fps <= 0.0 {
+            bail!(
+                "[smd Error] fps must be more than 0: `{}` -> `{}`",
+                fps,
+                new_fps
+            );
+        }
+        let frames = self.frame_count();
+        if frames < 2 {
+            return Ok(());
+        }
+        self.resample(conformed_frame_count(frames, fps, new_fps))
+    }
+}
+
+// the number of frames an animation has after conform_fps
+pub fn conformed_frame_count(frames: usize, fps: f64, new_fps: f64) -> usize {
+    if frames < 2 {
+        return frames;
+    }
+    ((frames - 1) as f64 * new_fps / fps).round() as usize + 1
+}
+
+// gets a bone's transform at a time between two frames
+fn sample(track: &BTreeMap<i32, &BoneFrame>, time: f64) -> BoneFrame {
+    let before = track.range(..=time.floor() as i32).next_back();
+    let after = track.range(time.ceil() as i32..).next();
+    match (before, after) {
+        (Some((&t0, &a)), Some((&t1, &b))) if t0 != t1 => {
+            let t = (time - t0 as f64) / (t1 - t0) as f64;
+            interpolate(a, b, t)
+        }
+        (Some((_, &bone)), _) | (None, Some((_, &bone))) => bone.clone(),
+        // tracks are only made for bones that have a transform
+        (None, None) => unreachable!(),
+    }
+}
+
+// interpolates between two transforms of a bone, t is from 0 to 1
+fn interpolate(a: &BoneFrame, b: &BoneFrame, t: f64) -> BoneFrame {
+    let mut position = [0.0; 3];
+    for (i, value) in position.iter_mut().enumerate() {
+        *value = a.position[i] + (b.position[i] - a.position[i]) * t;
+    }
+    BoneFrame {
+        index: a.index,
+        position,
+        rotation: quaternion_angles(slerp(
+            angle_quaternion(a.rotation),
+            angle_quaternion(b.rotation),
+            t,
+        )),
+    }
+}
+
+// smd rotations are radian euler angles applied x, then y, then z, like studiomdl's
+// AngleQuaternion
+fn angle_quaternion([x, y, z]: [f64; 3]) -> Quaternion {
+    let (sr, cr) = (x * 0.5).sin_cos();
+    let (sp, cp) = (y * 0.5).sin_cos();
+    let (sy, cy) = (z * 0.5).sin_cos();
+    [
+        sr * cp * cy - cr * sp * sy,
+        cr * sp * cy + sr * cp * sy,
+        cr * cp * sy - sr * sp * cy,
+        cr * cp * cy + sr * sp * sy,
+    ]
+}
+
+// the inverse of angle_quaternion
+fn quaternion_angles([x, y, z, w]: Quaternion) -> [f64; 3] {
+    let sin_pitch = (2.0 * (w * y - z * x)).clamp(-1.0, 1.0);
+    [
+        (2.0 * (w * x + y * z)).atan2(1.0 - 2.0 * (x * x + y * y)),
+        sin_pitch.asin(),
+        (2.0 * (w * z + x * y)).atan2(1.0 - 2.0 * (y * y + z * z)),
+    ]
+}
+
+// spherical linear interpolation, takes the shortest path
+fn slerp(a: Quaternion, mut b: Quaternion, t: f64) -> Quaternion {
+    let mut dot = a.iter().zip(b.iter()).map(|(a, b)| a * b).sum::<f64>();
+    if dot < 0.0 {
+        b.iter_mut().for_each(|value| *value = -*value);
+        dot = -dot;
+    }
+    // nearly the same rotation, lerp avoids dividing by ~0
+    let (scale_a, scale_b) = if dot > 0.9995 {
+        (1.0 - t, t)
+    } else {
+        let theta = dot.acos();
+        let sin_theta = theta.sin();
+        (
+            ((1.0 - t) * theta).sin() / sin_theta,
+            (t * theta).sin() / sin_theta,
+        )
+    };
+    let mut q = [0.0; 4];
+    for (i, value) in q.iter_mut().enumerate() {
+        *value = a[i] * scale_a + b[i] * scale_b;
+    }
+    let length = q.iter().map(|value| value * value).sum::<f64>().sqrt();
+    q.iter_mut().for_each(|value| *value /= length);
+    q
+}
@@ -0,0 +1,176 @@
+use crate::smd::Smd;
+use anyhow::{bail, Context, Result};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+// renames donor bones to base bones, names are compared ignoring case like studiomdl does
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct BoneMap(Vec<(String, String)>);
+
+impl BoneMap {
+    pub fn new<P: AsRef<Path>>(file: P) -> Result<BoneMap> {
+        let data = fs::read_to_string(&file)
+            .with_context(|| format!("Failed to read: {}", file.as_ref().display()))?;
+        Self::parse(data).with_context(|| format!("Failed to parse: {}", file.as_ref().display()))
+    }
+
+    // parses a bone-mapping table, one `"donor bone" "base bone"` pair per line
+    // quotes are only needed for names with spaces, `//` and `#` start comments
+    pub fn parse<T: AsRef<str>>(data: T) -> Result<BoneMap> {
+        let mut map = BoneMap::default();
+        for (idx, line) in data.as_ref().lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with("//") || line.starts_with('#') {
+                continue;
+            }
+            let names = split_names(line);
+            if names.len() != 2 {
+                bail!("[bone map Error] Weird line at {}: `{}`", idx + 1, line);
+            }
+            if map.get(&names[0]).is_some() {
+                bail!(
+                    "[bone map Error] `{}` is mapped twice at {}",
+                    names[0],
+                    idx + 1
+                );
+            }
+            map.0.push((names[0].to_owned(), names[1].to_owned()));
+        }
+        Ok(map)
+    }
+
+    // the base bone a donor bone is renamed to
+    pub fn get<T: AsRef<str>>(&self, name: T) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(from, _)| from.eq_ignore_ascii_case(name.as_ref()))
+            .map(|(_, to)| to.as_str())
+    }
+
+    // the name a donor bone has on the base skeleton
+    pub fn rename<'a>(&'a self, name: &'a str) -> &'a str {
+        self.get(name).unwrap_or(name)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+// splits a line into names, quoted names can have spaces
+fn split_names(line: &str) -> Vec<String> {
+    let mut names = Vec::new();
+    let mut chars = line.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '"' {
+            chars.next();
+            names.push(chars.by_ref().take_while(|&c| c != '"').collect());
+        } else {
+            let mut name = String::new();
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() {
+                    break;
+                }
+                name.push(c);
+                chars.next();
+            }
+            names.push(name);
+        }
+    }
+    names
+}
+
+impl Smd {
+    // renames the nodes with the bone map and reorders them to match the base skeleton,
+    // bones the skeleton doesn't have go after it in their original order
+    // the indices in `skeleton` and `triangles` are rewritten to match
+    pub fn retarget(&mut self, map: &BoneMap, skeleton: &[String]) -> Result<()> {
+        for node in self.nodes.iter_mut() {
+            node.name = map.rename(&node.name).to_owned();
+        }
+        for (i, node) in self.nodes.iter().enumerate() {
+            if let Some(other) = self.nodes[..i]
+                .iter()
+                .find(|other| other.name.eq_ignore_ascii_case(&node.name))
+            {
+                bail!(
+                    "[smd Error] Bones {} and {} would both be named `{}`",
+                    other.index,
+                    node.index,
+                    node.name
+                );
+            }
+        }
+
+        let position = |name: &str| {
+            skeleton
+                .iter()
+                .position(|bone| bone.eq_ignore_ascii_case(name))
+                .unwrap_or(skeleton.len())
+        };
+        // sort_by_key is stable, so bones that aren't in the skeleton keep their order
+        let mut nodes = self.nodes.clone();
+        nodes.sort_by_key(|node| position(&node.name));
+        let indices = nodes
+            .iter()
+            .enumerate()
+            .map(|(new, node)| (node.index, new as i32))
+            .collect::<HashMap<i32, i32>>();
+        let reindex = |index: i32| -> Result<i32> {
+            indices
+                .get(&index)
+                .copied()
+                .with_context(|| format!("[smd Error] Bone {} isn't in `nodes`", index))
+        };
+
+        for node in nodes.iter_mut() {
+            node.index = reindex(node.index)?;
+            if node.parent >= 0 {
+                node.parent = reindex(node.parent)?;
+            }
+        }
+        for frame in self.frames.iter_mut() {
+            for bone in frame.bones.iter_mut() {
+                bone.index = reindex(bone.index)?;
+            }
+            frame.bones.sort_by_key(|bone| bone.index);
+        }
+        // a material line, then 3 vertex lines for each triangle
+        for (i, line) in self.triangles.iter_mut().enumerate() {
+            if i % 4 != 0 {
+                *line = reindex_vertex(line, &reindex)?;
+            }
+        }
+        self.nodes = nodes;
+        Ok(())
+    }
+}
+
+// rewrites the bone indices of a `triangles` vertex line
+// `parent x y z nx ny nz u v [links bone weight ...]`
+fn reindex_vertex<F: Fn(i32) -> Result<i32>>(line: &str, reindex: &F) -> Result<String> {
+    let mut words = line
+        .split_whitespace()
+        .map(|word| word.to_owned())
+        .collect::<Vec<String>>();
+    let weird = || format!("[smd Error] Weird vertex: `{}`", line);
+    if words.len() < 9 {
+        bail!(weird());
+    }
+    let mut bones = vec![0];
+    if let Some(links) = words.get(9) {
+        let links = links.parse::<usize>().with_context(weird)?;
+        bones.extend((0..links).map(|link| 10 + link * 2));
+    }
+    for i in bones {
+        let index = words
+            .get(i)
+            .and_then(|index| index.parse::<i32>().ok())
+            .with_context(weird)?;
+        words[i] = reindex(index)?.to_string();
+    }
+    Ok(words.join(" "))
+}
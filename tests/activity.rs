@@ -0,0 +1,375 @@
+use qcmerger_rust::qc::activity;
+use qcmerger_rust::qc::QC;
+
+#[test]
+fn catalogue_test() {
+    assert!(activity::is_known("ACT_SECONDARY_VM_DRAW"));
+    assert!(activity::is_known("act_melee_vm_throw"));
+    assert!(!activity::is_known("ACT_SECONDRY_VM_DRAW"));
+
+    // every activity the fixtures use is in the catalogue
+    for qc_file in &[
+        "tests/scout/c_scout_animations.qc",
+        "tests/Pistol/c_scout_pistol.qc",
+    ] {
+        let qc = QC::new(qc_file).unwrap();
+        for seq in qc.sequences.values() {
+            assert!(
+                seq.activity.is_empty() || activity::is_known(&seq.activity),
+                "{}",
+                seq.activity
+            );
+        }
+    }
+}
+
+#[test]
+fn closest_test() {
+    assert_eq!(
+        activity::closest("ACT_SECONDRY_VM_DRAW"),
+        Some("ACT_SECONDARY_VM_DRAW")
+    );
+    assert_eq!(
+        activity::closest("act_primary_vm_relaod"),
+        Some("ACT_PRIMARY_VM_RELOAD")
+    );
+    assert_eq!(activity::closest("ACT_SECONDARY_VM_DRAW"), None);
+    assert_eq!(activity::closest("ACT_SOMETHING_ELSE_ENTIRELY"), None);
+}
+
+#[test]
+fn family_test() {
+    assert_eq!(activity::family("ACT_PRIMARY_RELOAD_START"), Some("reload"));
+    assert_eq!(
+        activity::family("ACT_MELEE_VM_INSPECT_IDLE"),
+        Some("inspect")
+    );
+    assert_eq!(activity::family("ACT_VM_IDLE_TO_LOWERED"), Some("lowered"));
+    assert_eq!(activity::family("ACT_SECONDARY_VM_DRAW_2"), Some("draw"));
+    assert_eq!(activity::family("ACT_MELEE_VM_SWINGHARD"), Some("attack"));
+    assert_eq!(activity::family("ACT_ITEM1_VM_IDLE"), Some("idle"));
+    assert_eq!(activity::family("ACT_THROWABLE_VM_IDLE"), Some("idle"));
+    assert_eq!(activity::family("ACT_BACKSTAB_VM_IDLE"), Some("idle"));
+    assert_eq!(activity::family("ACT_VM_RECOIL2"), Some("attack"));
+    assert_eq!(activity::family("ACT_VM_HARMLESS"), None);
+    assert_eq!(activity::family("ACT_NONE"), None);
+}
+
+#[test]
+fn catalogue_family_test() {
+    let families: [(&str, &[&str]); 7] = [
+        (
+            "inspect",
+            &[
+                "ACT_PRIMARY_VM_INSPECT_START",
+                "ACT_PRIMARY_VM_INSPECT_IDLE",
+                "ACT_PRIMARY_VM_INSPECT_END",
+                "ACT_SECONDARY_VM_INSPECT_START",
+                "ACT_SECONDARY_VM_INSPECT_IDLE",
+                "ACT_SECONDARY_VM_INSPECT_END",
+                "ACT_MELEE_VM_INSPECT_START",
+                "ACT_MELEE_VM_INSPECT_IDLE",
+                "ACT_MELEE_VM_INSPECT_END",
+                "ACT_MELEE_ALLCLASS_VM_INSPECT_START",
+                "ACT_MELEE_ALLCLASS_VM_INSPECT_IDLE",
+                "ACT_MELEE_ALLCLASS_VM_INSPECT_END",
+                "ACT_PRIMARY_ALT1_VM_INSPECT_START",
+                "ACT_PRIMARY_ALT1_VM_INSPECT_IDLE",
+                "ACT_PRIMARY_ALT1_VM_INSPECT_END",
+                "ACT_SECONDARY_ALT1_VM_INSPECT_START",
+                "ACT_SECONDARY_ALT1_VM_INSPECT_IDLE",
+                "ACT_SECONDARY_ALT1_VM_INSPECT_END",
+                "ACT_MELEE_ALT1_VM_INSPECT_START",
+                "ACT_MELEE_ALT1_VM_INSPECT_IDLE",
+                "ACT_MELEE_ALT1_VM_INSPECT_END",
+                "ACT_PRIMARY_ALT2_VM_INSPECT_START",
+                "ACT_PRIMARY_ALT2_VM_INSPECT_IDLE",
+                "ACT_PRIMARY_ALT2_VM_INSPECT_END",
+                "ACT_SECONDARY_ALT2_VM_INSPECT_START",
+                "ACT_SECONDARY_ALT2_VM_INSPECT_IDLE",
+                "ACT_SECONDARY_ALT2_VM_INSPECT_END",
+                "ACT_MELEE_ALT2_VM_INSPECT_START",
+                "ACT_MELEE_ALT2_VM_INSPECT_IDLE",
+                "ACT_MELEE_ALT2_VM_INSPECT_END",
+                "ACT_PRIMARY_ALT3_VM_INSPECT_START",
+                "ACT_PRIMARY_ALT3_VM_INSPECT_IDLE",
+                "ACT_PRIMARY_ALT3_VM_INSPECT_END",
+                "ACT_SECONDARY_ALT3_VM_INSPECT_START",
+                "ACT_SECONDARY_ALT3_VM_INSPECT_IDLE",
+                "ACT_SECONDARY_ALT3_VM_INSPECT_END",
+                "ACT_MELEE_ALT3_VM_INSPECT_START",
+                "ACT_MELEE_ALT3_VM_INSPECT_IDLE",
+                "ACT_MELEE_ALT3_VM_INSPECT_END",
+                "ACT_ITEM1_VM_INSPECT_START",
+                "ACT_ITEM1_VM_INSPECT_IDLE",
+                "ACT_ITEM1_VM_INSPECT_END",
+                "ACT_ITEM2_VM_INSPECT_START",
+                "ACT_ITEM2_VM_INSPECT_IDLE",
+                "ACT_ITEM2_VM_INSPECT_END",
+                "ACT_BREADMONSTER_VM_INSPECT_START",
+                "ACT_BREADMONSTER_VM_INSPECT_IDLE",
+                "ACT_BREADMONSTER_VM_INSPECT_END",
+            ],
+        ),
+        (
+            "reload",
+            &[
+                "ACT_VM_RELOAD",
+                "ACT_PRIMARY_VM_RELOAD",
+                "ACT_PRIMARY_RELOAD_START",
+                "ACT_PRIMARY_RELOAD_FINISH",
+                "ACT_PRIMARY_VM_RELOAD_2",
+                "ACT_PRIMARY_RELOAD_START_2",
+                "ACT_PRIMARY_RELOAD_FINISH_2",
+                "ACT_PRIMARY_VM_RELOAD_3",
+                "ACT_PRIMARY_RELOAD_START_3",
+                "ACT_PRIMARY_RELOAD_FINISH_3",
+                "ACT_SECONDARY_VM_RELOAD",
+                "ACT_SECONDARY_RELOAD_START",
+                "ACT_SECONDARY_RELOAD_FINISH",
+                "ACT_SECONDARY_VM_RELOAD_2",
+                "ACT_SECONDARY_VM_RELOAD2",
+                "ACT_MELEE_VM_RELOAD",
+                "ACT_MELEE_ALLCLASS_VM_RELOAD",
+                "ACT_PDA_VM_RELOAD",
+                "ACT_ITEM1_VM_RELOAD",
+                "ACT_ITEM2_VM_RELOAD",
+                "ACT_ITEM3_VM_RELOAD",
+                "ACT_ITEM4_VM_RELOAD",
+            ],
+        ),
+        (
+            "lowered",
+            &[
+                "ACT_VM_IDLE_TO_LOWERED",
+                "ACT_VM_IDLE_LOWERED",
+                "ACT_VM_LOWERED_TO_IDLE",
+                "ACT_PRIMARY_VM_IDLE_TO_LOWERED",
+                "ACT_PRIMARY_VM_IDLE_LOWERED",
+                "ACT_PRIMARY_VM_LOWERED_TO_IDLE",
+                "ACT_SECONDARY_VM_IDLE_TO_LOWERED",
+                "ACT_SECONDARY_VM_IDLE_LOWERED",
+                "ACT_SECONDARY_VM_LOWERED_TO_IDLE",
+                "ACT_MELEE_VM_IDLE_TO_LOWERED",
+                "ACT_MELEE_VM_IDLE_LOWERED",
+                "ACT_MELEE_VM_LOWERED_TO_IDLE",
+                "ACT_MELEE_ALLCLASS_VM_IDLE_TO_LOWERED",
+                "ACT_MELEE_ALLCLASS_VM_IDLE_LOWERED",
+                "ACT_MELEE_ALLCLASS_VM_LOWERED_TO_IDLE",
+                "ACT_PDA_VM_IDLE_TO_LOWERED",
+                "ACT_PDA_VM_IDLE_LOWERED",
+                "ACT_PDA_VM_LOWERED_TO_IDLE",
+                "ACT_ITEM1_VM_IDLE_TO_LOWERED",
+                "ACT_ITEM1_VM_IDLE_LOWERED",
+                "ACT_ITEM1_VM_LOWERED_TO_IDLE",
+                "ACT_ITEM2_VM_IDLE_TO_LOWERED",
+                "ACT_ITEM2_VM_IDLE_LOWERED",
+                "ACT_ITEM2_VM_LOWERED_TO_IDLE",
+                "ACT_ITEM3_VM_IDLE_TO_LOWERED",
+                "ACT_ITEM3_VM_IDLE_LOWERED",
+                "ACT_ITEM3_VM_LOWERED_TO_IDLE",
+                "ACT_ITEM4_VM_IDLE_TO_LOWERED",
+                "ACT_ITEM4_VM_IDLE_LOWERED",
+                "ACT_ITEM4_VM_LOWERED_TO_IDLE",
+                "ACT_VM_IDLE_TO_LOWERED_SPECIAL",
+                "ACT_VM_IDLE_LOWERED_SPECIAL",
+                "ACT_VM_LOWERED_TO_IDLE_SPECIAL",
+            ],
+        ),
+        (
+            "draw",
+            &[
+                "ACT_VM_DRAW",
+                "ACT_VM_PICKUP",
+                "ACT_PRIMARY_VM_DRAW",
+                "ACT_SECONDARY_VM_DRAW",
+                "ACT_SECONDARY_VM_DRAW_2",
+                "ACT_MELEE_VM_DRAW",
+                "ACT_MELEE_ALLCLASS_VM_DRAW",
+                "ACT_PDA_VM_DRAW",
+                "ACT_ENGINEER_PDA1_VM_DRAW",
+                "ACT_ENGINEER_PDA2_VM_DRAW",
+                "ACT_ENGINEER_BLD_VM_DRAW",
+                "ACT_ITEM1_VM_DRAW",
+                "ACT_ITEM2_VM_DRAW",
+                "ACT_ITEM3_VM_DRAW",
+                "ACT_ITEM4_VM_DRAW",
+                "ACT_VM_DRAW_SPECIAL",
+                "ACT_BALL_VM_PICKUP",
+                "ACT_GRAPPLE_DRAW",
+                "ACT_SPELL_VM_DRAW",
+                "ACT_THROWABLE_VM_DRAW",
+                "ACT_BREADMONSTER_VM_DRAW",
+            ],
+        ),
+        (
+            "holster",
+            &[
+                "ACT_VM_HOLSTER",
+                "ACT_PRIMARY_VM_HOLSTER",
+                "ACT_SECONDARY_VM_HOLSTER",
+                "ACT_MELEE_VM_HOLSTER",
+                "ACT_MELEE_ALLCLASS_VM_HOLSTER",
+                "ACT_PDA_VM_HOLSTER",
+                "ACT_ITEM1_VM_HOLSTER",
+                "ACT_ITEM2_VM_HOLSTER",
+                "ACT_ITEM3_VM_HOLSTER",
+                "ACT_ITEM4_VM_HOLSTER",
+                "ACT_VM_HOLSTER_SPECIAL",
+            ],
+        ),
+        (
+            "attack",
+            &[
+                "ACT_VM_PULLBACK",
+                "ACT_VM_PULLBACK_HIGH",
+                "ACT_VM_PULLBACK_LOW",
+                "ACT_VM_THROW",
+                "ACT_VM_PULLPIN",
+                "ACT_VM_PRIMARYATTACK",
+                "ACT_VM_SECONDARYATTACK",
+                "ACT_VM_DRYFIRE",
+                "ACT_VM_HITLEFT",
+                "ACT_VM_HITLEFT2",
+                "ACT_VM_HITRIGHT",
+                "ACT_VM_HITRIGHT2",
+                "ACT_VM_HITCENTER",
+                "ACT_VM_HITCENTER2",
+                "ACT_VM_MISSLEFT",
+                "ACT_VM_MISSLEFT2",
+                "ACT_VM_MISSRIGHT",
+                "ACT_VM_MISSRIGHT2",
+                "ACT_VM_MISSCENTER",
+                "ACT_VM_MISSCENTER2",
+                "ACT_VM_HAULBACK",
+                "ACT_VM_SWINGHARD",
+                "ACT_VM_SWINGMISS",
+                "ACT_VM_SWINGHIT",
+                "ACT_VM_RECOIL1",
+                "ACT_VM_RECOIL2",
+                "ACT_VM_RECOIL3",
+                "ACT_VM_RELEASE",
+                "ACT_PRIMARY_VM_PULLBACK",
+                "ACT_PRIMARY_VM_PRIMARYATTACK",
+                "ACT_PRIMARY_VM_SECONDARYATTACK",
+                "ACT_PRIMARY_VM_DRYFIRE",
+                "ACT_PRIMARY_VM_PRIMARYATTACK_3",
+                "ACT_SECONDARY_VM_PULLBACK",
+                "ACT_SECONDARY_VM_PRIMARYATTACK",
+                "ACT_SECONDARY_VM_SECONDARYATTACK",
+                "ACT_SECONDARY_VM_DRYFIRE",
+                "ACT_SECONDARY_VM_ALTATTACK",
+                "ACT_SECONDARY_VM_PRIMARYATTACK_2",
+                "ACT_MELEE_VM_PULLBACK",
+                "ACT_MELEE_VM_PRIMARYATTACK",
+                "ACT_MELEE_VM_SECONDARYATTACK",
+                "ACT_MELEE_VM_DRYFIRE",
+                "ACT_MELEE_VM_STUN",
+                "ACT_MELEE_VM_HITCENTER",
+                "ACT_MELEE_VM_SWINGHARD",
+                "ACT_MELEE_VM_THROW",
+                "ACT_MELEE_ALLCLASS_VM_PULLBACK",
+                "ACT_MELEE_ALLCLASS_VM_PRIMARYATTACK",
+                "ACT_MELEE_ALLCLASS_VM_SECONDARYATTACK",
+                "ACT_MELEE_ALLCLASS_VM_DRYFIRE",
+                "ACT_MELEE_ALLCLASS_VM_STUN",
+                "ACT_MELEE_ALLCLASS_VM_HITCENTER",
+                "ACT_MELEE_ALLCLASS_VM_SWINGHARD",
+                "ACT_PDA_VM_PULLBACK",
+                "ACT_PDA_VM_PRIMARYATTACK",
+                "ACT_PDA_VM_SECONDARYATTACK",
+                "ACT_PDA_VM_DRYFIRE",
+                "ACT_ITEM1_VM_PULLBACK",
+                "ACT_ITEM1_VM_PRIMARYATTACK",
+                "ACT_ITEM1_VM_SECONDARYATTACK",
+                "ACT_ITEM1_VM_DRYFIRE",
+                "ACT_ITEM2_VM_PULLBACK",
+                "ACT_ITEM2_VM_PRIMARYATTACK",
+                "ACT_ITEM2_VM_SECONDARYATTACK",
+                "ACT_ITEM2_VM_DRYFIRE",
+                "ACT_ITEM3_VM_PULLBACK",
+                "ACT_ITEM3_VM_PRIMARYATTACK",
+                "ACT_ITEM3_VM_SECONDARYATTACK",
+                "ACT_ITEM3_VM_DRYFIRE",
+                "ACT_ITEM3_VM_THROW",
+                "ACT_ITEM4_VM_PULLBACK",
+                "ACT_ITEM4_VM_PRIMARYATTACK",
+                "ACT_ITEM4_VM_SECONDARYATTACK",
+                "ACT_ITEM4_VM_DRYFIRE",
+                "ACT_VM_PULLBACK_SPECIAL",
+                "ACT_VM_PRIMARYATTACK_SPECIAL",
+                "ACT_VM_SECONDARYATTACK_SPECIAL",
+                "ACT_VM_HITCENTER_SPECIAL",
+                "ACT_VM_SWINGHARD_SPECIAL",
+                "ACT_BACKSTAB_VM_DOWN",
+                "ACT_BACKSTAB_VM_UP",
+                "ACT_BALL_VM_CATCH",
+                "ACT_BALL_VM_THROW_START",
+                "ACT_BALL_VM_THROW_LOOP",
+                "ACT_BALL_VM_THROW_END",
+                "ACT_GRAPPLE_FIRE_START",
+                "ACT_GRAPPLE_PULL_START",
+                "ACT_GRAPPLE_PULL_END",
+                "ACT_SPELL_VM_ARM",
+                "ACT_SPELL_VM_FIRE",
+                "ACT_THROWABLE_VM_FIRE",
+                "ACT_BREADMONSTER_VM_PRIMARYATTACK",
+            ],
+        ),
+        (
+            "idle",
+            &[
+                "ACT_VM_IDLE",
+                "ACT_VM_FIDGET",
+                "ACT_PRIMARY_VM_IDLE",
+                "ACT_SECONDARY_VM_IDLE",
+                "ACT_SECONDARY_VM_IDLE_2",
+                "ACT_MELEE_VM_IDLE",
+                "ACT_MELEE_ALLCLASS_VM_IDLE",
+                "ACT_PDA_VM_IDLE",
+                "ACT_ENGINEER_PDA1_VM_IDLE",
+                "ACT_ENGINEER_PDA2_VM_IDLE",
+                "ACT_ENGINEER_BLD_VM_IDLE",
+                "ACT_ITEM1_VM_IDLE",
+                "ACT_ITEM2_VM_IDLE",
+                "ACT_ITEM3_VM_IDLE",
+                "ACT_ITEM4_VM_IDLE",
+                "ACT_VM_IDLE_SPECIAL",
+                "ACT_BACKSTAB_VM_IDLE",
+                "ACT_BALL_VM_IDLE",
+                "ACT_GRAPPLE_IDLE",
+                "ACT_GRAPPLE_FIRE_IDLE",
+                "ACT_GRAPPLE_PULL_IDLE",
+                "ACT_SPELL_VM_IDLE",
+                "ACT_THROWABLE_VM_IDLE",
+                "ACT_BREADMONSTER_VM_IDLE",
+            ],
+        ),
+    ];
+    for (family, activities) in &families {
+        for activity in activities.iter() {
+            assert_eq!(activity::family(activity), Some(*family), "{}", activity);
+        }
+    }
+    // every activity in the catalogue is checked
+    let checked = families
+        .iter()
+        .flat_map(|(_, activities)| activities.iter())
+        .collect::<Vec<_>>();
+    assert_eq!(checked.len(), activity::ACTIVITIES.len());
+    for activity in &activity::ACTIVITIES {
+        assert!(checked.contains(&activity), "{}", activity);
+    }
+}
+
+#[test]
+fn activity_slots_test() {
+    let qc = QC::new("tests/Pistol/c_scout_pistol.qc").unwrap();
+    let slots = qc
+        .activity_slots("ACT_VM_DRAW")
+        .iter()
+        .map(|seq| seq.name.as_str())
+        .collect::<Vec<&str>>();
+    assert_eq!(slots, vec!["p_draw"]);
+    assert!(qc.activity_slots("ACT_NONE").is_empty());
+}
@@ -1,4 +1,5 @@
 use qcmerger_rust::qc::animation::Animation;
+use qcmerger_rust::qc::QC;
 
 #[test]
 fn parse_test() {
@@ -9,8 +10,8 @@ loop
 weightlist "weights_r_handposes"
 }"#,
     );
-    let mut y: Vec<String> = x.lines().map(|l| l.to_owned()).collect();
-    let z = Animation::parse(&mut y, 0).unwrap();
+    let y: Vec<String> = x.lines().map(|l| l.to_owned()).collect();
+    let z = Animation::parse(&y, 0).unwrap();
     assert_eq!(z.name, "test_animation");
     assert_eq!(z.start, 0usize);
     assert_eq!(z.end, 4usize);
@@ -32,8 +33,8 @@ loop
 weightlist "weights_r_handposes"
 }"#,
     );
-    let mut y: Vec<String> = x.lines().map(|l| l.to_owned()).collect();
-    let _ = Animation::parse(&mut y, 0).unwrap();
+    let y: Vec<String> = x.lines().map(|l| l.to_owned()).collect();
+    let _ = Animation::parse(&y, 0).unwrap();
 }
 
 #[test]
@@ -42,8 +43,8 @@ fn parse_test_single_line() {
         "\
 $animation \"test_layer_anim\" \"c_scout_animations_anims\\test_layer.smd\" loop subtract idle 0 ",
     );
-    let mut y: Vec<String> = x.lines().map(|l| l.to_owned()).collect();
-    let z = Animation::parse(&mut y, 0).unwrap();
+    let y: Vec<String> = x.lines().map(|l| l.to_owned()).collect();
+    let z = Animation::parse(&y, 0).unwrap();
     assert_eq!(z.smd, r"c_scout_animations_anims\test_layer.smd");
     assert_eq!(z.name, "test_layer_anim");
     assert_eq!(z.start, 0usize);
@@ -57,6 +58,102 @@ fn parse_test_fail_single_line() {
         r#"
 $animation "test_layer_anim" "c_scout_animations_anims\test_layer.smd" {loop subtract idle 0} "#,
     );
-    let mut y: Vec<String> = x.lines().map(|l| l.to_owned()).collect();
-    let _ = Animation::parse(&mut y, 0).unwrap();
+    let y: Vec<String> = x.lines().map(|l| l.to_owned()).collect();
+    let _ = Animation::parse(&y, 0).unwrap();
+}
+
+#[test]
+fn same_as_test() {
+    let parse = |block: &str| {
+        Animation::parse(
+            &block
+                .lines()
+                .map(|line| line.to_owned())
+                .collect::<Vec<String>>(),
+            0,
+        )
+        .unwrap()
+    };
+    let base = parse("$animation \"p_idle_anim\" \"p_idle.smd\" {\nloop\n}");
+    assert!(base.same_as(&parse("$animation p_idle_anim \"p_idle.smd\" {\nloop\n}")));
+    assert!(!base.same_as(&parse(
+        "$animation \"p_idle_anim\" \"p_idle.smd\" {\nfps 24\n}"
+    )));
+    assert!(!base.same_as(&parse(
+        "$animation \"p_idle_anim\" \"other.smd\" {\nloop\n}"
+    )));
+
+    // a single-line block has its options in the header
+    let base = parse("$animation \"p_idle_anim\" \"p_idle.smd\" fps 30 loop");
+    assert!(base.same_as(&parse("$animation p_idle_anim \"p_idle.smd\" fps 30 loop")));
+    assert!(!base.same_as(&parse(
+        "$animation \"p_idle_anim\" \"p_idle.smd\" fps 24 loop"
+    )));
+}
+
+#[test]
+fn rename_animation_test() {
+    let mut qc = QC::parse(
+        "donor.qc",
+        r#"$animation "p_idle_anim" "p_idle.smd" {
+loop
+}
+$sequence "p_idle" "p_idle_anim" {
+activity "ACT_SECONDARY_VM_IDLE" 1
+}
+$sequence "p_idle_2" {
+"p_idle_anim"
+}
+$sequence "p_draw" "p_draw.smd""#
+            .lines()
+            .map(|line| line.to_owned()),
+    )
+    .unwrap();
+    assert_eq!(
+        qc.used_animations(&["p_idle".to_owned(), "p_draw".to_owned()]),
+        vec!["p_idle_anim"]
+    );
+
+    qc.rename_animation("p_idle_anim", "p_idle_anim_donor")
+        .unwrap();
+    assert_eq!(
+        qc.animations["p_idle_anim_donor"].block[0],
+        r#"$animation "p_idle_anim_donor" "p_idle.smd" {"#
+    );
+    assert_eq!(
+        qc.sequences["p_idle"].block[0],
+        r#"$sequence "p_idle" "p_idle_anim_donor" {"#
+    );
+    assert_eq!(qc.sequences["p_idle_2"].smd, "p_idle_anim_donor");
+    assert_eq!(
+        qc.sequences["p_idle_2"].write_block()[1],
+        r#""p_idle_anim_donor""#
+    );
+    assert!(qc.rename_animation("p_idle_anim", "other").is_err());
+}
+
+#[test]
+fn replace_animation_test() {
+    let qc_data = r#"$animation "p_idle_anim" "p_idle.smd" {
+loop
+}
+$sequence "p_idle" "p_idle_anim""#
+        .lines()
+        .map(|line| line.to_owned())
+        .collect::<Vec<String>>();
+    let mut qc = QC::parse("base.qc", qc_data.clone().into_iter()).unwrap();
+    let donor = QC::parse(
+        "donor.qc",
+        r#"$animation "p_idle_anim" "donor_idle.smd" {
+fps 24
+loop
+}"#
+        .lines()
+        .map(|line| line.to_owned()),
+    )
+    .unwrap();
+    qc.replace_animation(&donor, "p_idle_anim").unwrap();
+    assert_eq!(qc.animations.len(), 1);
+    assert_eq!(qc.animations["p_idle_anim"].smd, "donor_idle.smd");
+    assert_eq!(qc.document.lines()[4], qc.sequences["p_idle"].block[0]);
 }
@@ -0,0 +1,130 @@
+use qcmerger_rust::cli::{process_input, process_overrides, Selection};
+use qcmerger_rust::qc::options::OptionOverride;
+use qcmerger_rust::qc::QC;
+
+#[test]
+fn process_input_test() {
+    let x = QC::new("tests/Pistol/c_scout_pistol.qc").unwrap();
+    let y = process_input(&x, "p_draw p_re*").unwrap();
+    assert_eq!(
+        y,
+        vec![
+            Selection::Toggle("p_draw".to_owned()),
+            Selection::Toggle("p_reload".to_owned())
+        ]
+    );
+
+    let y = process_input(&x, "!p_*").unwrap();
+    assert_eq!(y.len(), 4);
+    assert!(y.iter().all(|s| matches!(s, Selection::Deselect(_))));
+}
+
+#[test]
+fn process_input_test_selectors() {
+    let x = QC::new("tests/Pistol/c_scout_pistol.qc").unwrap();
+    let y = process_input(&x, "re:/^p_(draw|idle)$/").unwrap();
+    assert_eq!(
+        y,
+        vec![
+            Selection::Toggle("p_draw".to_owned()),
+            Selection::Toggle("p_idle".to_owned())
+        ]
+    );
+
+    let y = process_input(&x, "act:ACT_SECONDARY_VM_RELOAD").unwrap();
+    assert_eq!(y, vec![Selection::Toggle("p_reload".to_owned())]);
+
+    let x = QC::new("tests/scout/c_scout_animations.qc").unwrap();
+    let y = process_input(&x, "uses:test_weight").unwrap();
+    assert!(y.contains(&Selection::Toggle("p_draw".to_owned())));
+    let y = process_input(&x, "!uses:weights_r_handposes").unwrap();
+    assert!(!y.is_empty());
+}
+
+#[test]
+#[should_panic]
+fn process_input_test_fail() {
+    let x = QC::new("tests/Pistol/c_scout_pistol.qc").unwrap();
+    let _ = process_input(&x, "p_draw non_existent").unwrap();
+}
+
+#[test]
+#[should_panic]
+fn process_input_test_fail_regex() {
+    let x = QC::new("tests/Pistol/c_scout_pistol.qc").unwrap();
+    let _ = process_input(&x, "re:^p_draw").unwrap();
+}
+
+#[test]
+fn process_input_test_overrides() {
+    let x = QC::new("tests/Pistol/c_scout_pistol.qc").unwrap();
+    let fps = OptionOverride::parse("fps=24").unwrap();
+    let node = OptionOverride::parse("node=base").unwrap();
+    let y = process_input(&x, "p_draw{fps=24,node=base} p_idle").unwrap();
+    assert_eq!(
+        y,
+        vec![
+            Selection::Override("p_draw".to_owned(), vec![fps.clone(), node]),
+            Selection::Toggle("p_idle".to_owned())
+        ]
+    );
+
+    // only the last `{` starts the options
+    let y = process_input(&x, "re:/^p_dr(a){1}w$/{fps=24}").unwrap();
+    assert_eq!(
+        y,
+        vec![Selection::Override("p_draw".to_owned(), vec![fps.clone()])]
+    );
+
+    assert!(process_input(&x, "!p_draw{fps=24}").is_err());
+    assert!(process_input(&x, "p_draw{fps}").is_err());
+
+    let y = process_overrides(&x, &["p_dr*{fps=24}".to_owned()]).unwrap();
+    assert_eq!(y["p_draw"], vec![fps]);
+    assert!(process_overrides(&x, &["p_draw".to_owned()]).is_err());
+}
+
+#[test]
+fn process_input_test_alternates() {
+    let x = QC::new("tests/Pistol/c_scout_pistol.qc").unwrap();
+    let y = process_input(&x, "+p_draw +p_idle{fadein=base}").unwrap();
+    assert_eq!(
+        y,
+        vec![
+            Selection::Alternate("p_draw".to_owned(), Vec::new()),
+            Selection::Alternate(
+                "p_idle".to_owned(),
+                vec![OptionOverride::parse("fadein=base").unwrap()]
+            ),
+        ]
+    );
+}
+
+#[test]
+fn process_input_test_families() {
+    let x = QC::new("tests/Pistol/c_scout_pistol.qc").unwrap();
+    let y = process_input(&x, "fam:Reload").unwrap();
+    assert_eq!(y, vec![Selection::Toggle("p_reload".to_owned())]);
+    assert!(process_input(&x, "fam:relaod").is_err());
+}
+
+#[test]
+fn process_input_test_activities() {
+    let x = QC::parse(
+        "test.qc",
+        r#"$sequence "p_idle" "p_idle.smd" {
+activity "ACT_VM_IDLE" 1
+activity "ACT_SECONDARY_VM_RELOAD" 1
+}
+$sequence "p_none" "p_none.smd""#
+            .lines()
+            .map(|line| line.to_owned()),
+    )
+    .unwrap();
+    // every activity of a sequence is matched, not only the first
+    let idle = vec![Selection::Toggle("p_idle".to_owned())];
+    assert_eq!(process_input(&x, "act:*_RELOAD").unwrap(), idle);
+    assert_eq!(process_input(&x, "fam:reload").unwrap(), idle);
+    assert_eq!(process_input(&x, "fam:idle").unwrap(), idle);
+    assert_eq!(process_input(&x, "act:*").unwrap().len(), 2);
+}
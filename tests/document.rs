@@ -0,0 +1,118 @@
+use qcmerger_rust::qc::document::Document;
+use qcmerger_rust::qc::QC;
+
+#[test]
+fn document_test() {
+    let mut document = Document::default();
+    let first = document.push(vec!["a".to_owned(), "b".to_owned()]);
+    let last = document.push(vec!["c".to_owned()]);
+    let before = document.insert_before(first, vec!["start".to_owned()]);
+    document.insert_after(first, vec!["after a b".to_owned()]);
+    document.insert_before(last, vec!["before c".to_owned()]);
+    assert_eq!(
+        document.lines(),
+        ["start", "a", "b", "after a b", "before c", "c"]
+    );
+
+    // the handles still point to the same blocks after the inserts
+    document.replace(first, vec!["x".to_owned()]);
+    assert_eq!(document.block(first), ["x"]);
+    assert_eq!(document.block(before), ["start"]);
+    assert_eq!(
+        document.lines(),
+        ["start", "x", "after a b", "before c", "c"]
+    );
+    let (before_first, from_first) = document.split(first);
+    assert_eq!(before_first, ["start"]);
+    assert_eq!(from_first, ["x", "after a b", "before c", "c"]);
+
+    // comments stay above their block when it's replaced or a block is inserted before it
+    document.annotate(last, vec!["// c".to_owned()]);
+    document.replace(last, vec!["d".to_owned()]);
+    document.insert_before(last, vec!["before d".to_owned()]);
+    assert_eq!(document.block(last), ["d"]);
+    assert_eq!(
+        document.lines(),
+        [
+            "start",
+            "x",
+            "after a b",
+            "before c",
+            "before d",
+            "// c",
+            "d"
+        ]
+    );
+}
+
+#[test]
+fn parse_document_test() {
+    let qc_data = r#"$modelname "test.mdl"
+/* $sequence "commented" "commented.smd" {
+} */
+$weightlist "test_weight" {
+"root" 0
+}
+$pushd "anims"
+$sequence "p_draw" "p_draw.smd" {
+}
+$popd
+// the end"#
+        .lines()
+        .map(|line| line.to_owned())
+        .collect::<Vec<String>>();
+    let qc = QC::parse("test.qc", qc_data.clone().into_iter()).unwrap();
+    assert_eq!(qc.document.lines(), qc_data);
+    let p_draw = &qc.sequences["p_draw"];
+    assert_eq!(qc.document.block(p_draw.id), p_draw.block.as_slice());
+    assert_eq!(qc_data[7], p_draw.block[0]);
+    assert_eq!(qc_data[3], qc.weightlists["test_weight"].block[0]);
+    assert!(!qc.sequences.contains_key("commented"));
+}
+
+#[test]
+fn append_edit_test() {
+    let mut qc = QC::parse(
+        "base.qc",
+        r#"$pushd "anims"
+$sequence "p_idle" "p_idle.smd" {
+}
+$popd"#
+            .lines()
+            .map(|line| line.to_owned()),
+    )
+    .unwrap();
+    let donor = QC::parse(
+        "donor.qc",
+        r#"$animation "p_draw_anim" "p_draw.smd" {
+}
+$sequence "p_draw" "p_draw_anim" {
+fps 30
+}"#
+        .lines()
+        .map(|line| line.to_owned()),
+    )
+    .unwrap();
+    qc.append_sequence(&donor, "p_draw").unwrap();
+    qc.append_animation(&donor, "p_draw_anim").unwrap();
+    // the appended blocks can be edited like the base's
+    qc.set_fps("p_draw", 24.0).unwrap();
+    assert_eq!(
+        qc.document.lines(),
+        r#"$pushd "anims"
+$animation "p_draw_anim" "p_draw.smd" {
+}
+$sequence "p_idle" "p_idle.smd" {
+}
+$sequence "p_draw" "p_draw_anim" {
+fps 24
+}
+$popd"#
+            .lines()
+            .collect::<Vec<&str>>()
+    );
+    let p_draw = &qc.sequences["p_draw"];
+    assert!(p_draw.uses_animation);
+    assert_eq!(p_draw.dir, "anims");
+    assert_eq!(qc.animations["p_draw_anim"].dir, "anims");
+}
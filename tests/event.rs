@@ -0,0 +1,129 @@
+use qcmerger_rust::qc::event::Event;
+use qcmerger_rust::qc::QC;
+use std::collections::HashMap;
+
+#[test]
+fn parse_test() {
+    let event = Event::parse(r#"{ event 5004 18 "Weapon_Pistol.ClipIn" }"#).unwrap();
+    assert_eq!(
+        event,
+        Event {
+            event: "5004".to_owned(),
+            frame: 18,
+            options: "Weapon_Pistol.ClipIn".to_owned()
+        }
+    );
+    assert!(event.is_sound());
+    assert_eq!(
+        event.to_string(),
+        r#"{ event 5004 18 "Weapon_Pistol.ClipIn" }"#
+    );
+
+    let event = Event::parse(
+        r#"{ event AE_CL_CREATE_PARTICLE_EFFECT 5 "energydrink_splash follow_attachment drink_spray" }"#,
+    )
+    .unwrap();
+    assert_eq!(
+        event.options,
+        "energydrink_splash follow_attachment drink_spray"
+    );
+    assert!(!event.is_sound());
+
+    assert_eq!(
+        Event::parse("event 6002 5").unwrap().to_string(),
+        "{ event 6002 5 }"
+    );
+    assert!(Event::parse(r#"// { event 5004 1 "Weapon_Pistol.ReloadScout" }"#).is_none());
+    assert!(Event::parse("events 5004 1").is_none());
+    assert!(Event::parse("{ event 5004 }").is_none());
+}
+
+#[test]
+fn rescale_test() {
+    let mut event = Event::parse(r#"{ event 5004 18 "Weapon_Pistol.ClipIn" }"#).unwrap();
+    // frame 18 of 0..=20 is frame 9 of 0..=10
+    event.rescale(21, 11);
+    assert_eq!(event.frame, 9);
+    event.rescale(1, 11);
+    assert_eq!(event.frame, 9);
+}
+
+#[test]
+fn replace_sound_events_test() {
+    let mut qc_data = r#"$sequence "p_reload" "p_reload.smd" {
+activity "ACT_SECONDARY_VM_RELOAD" 1
+{ event 5004 1 "Weapon_Pistol.ReloadScout" }
+{ event 6002 5 "eject_brass" }
+{ event 5004 18 "Weapon_Pistol.ClipIn" }
+fps 30
+}
+$sequence "p_idle" "p_idle.smd" {
+}"#
+    .lines()
+    .map(|line| line.to_owned())
+    .collect::<Vec<String>>();
+    let mut qc = QC::parse("test.qc", qc_data.clone().into_iter()).unwrap();
+    assert_eq!(qc.sequences["p_reload"].options.events.len(), 3);
+
+    let events = vec![Event::parse(r#"{ event 5004 9 "Weapon_Pistol.ClipIn" }"#).unwrap()];
+    qc.replace_sound_events("p_reload", &events).unwrap();
+    qc_data = qc.document.lines();
+    assert_eq!(
+        qc_data[1..5],
+        [
+            r#"activity "ACT_SECONDARY_VM_RELOAD" 1"#,
+            r#"{ event 5004 9 "Weapon_Pistol.ClipIn" }"#,
+            r#"{ event 6002 5 "eject_brass" }"#,
+            "fps 30",
+        ]
+    );
+    assert_eq!(qc.sequences["p_reload"].options.events.len(), 2);
+    assert_eq!(qc_data[6], qc.sequences["p_idle"].block[0]);
+
+    // a $sequence without sound events gets them before its `}`
+    qc.replace_sound_events("p_idle", &events).unwrap();
+    qc_data = qc.document.lines();
+    assert_eq!(qc_data[7], events[0].to_string());
+    assert_eq!(qc.sequences["p_idle"].options.events, events);
+}
+
+#[test]
+fn frame_count_test() {
+    let qc_base = QC::new("tests/scout/c_scout_animations.qc").unwrap();
+    let qc_file = QC::new("tests/Pistol/c_scout_pistol.qc").unwrap();
+    let relocated = HashMap::new();
+    // `frames 89 125` of the 126 frames of c_scout_arms_skeleton.smd
+    assert_eq!(qc_base.frame_count("p_reload", &relocated), Some(37));
+    assert_eq!(qc_base.frame_count("p_draw", &relocated), Some(21));
+    // the donor has no range, it plays the whole smd
+    assert_eq!(qc_file.frame_count("p_reload", &relocated), Some(37));
+    assert_eq!(qc_file.frame_count("p_draw", &relocated), Some(21));
+
+    // the events of the base keep their frames on a donor as long as its range
+    for (name, frame) in [("p_reload", 19), ("p_draw", 1)].iter() {
+        let mut event = qc_base.sequences[*name]
+            .options
+            .events
+            .iter()
+            .find(|event| event.is_sound())
+            .cloned()
+            .unwrap();
+        event.rescale(
+            qc_base.frame_count(name, &relocated).unwrap(),
+            qc_file.frame_count(name, &relocated).unwrap(),
+        );
+        assert_eq!(event.frame, *frame);
+    }
+
+    // `frame start end` counts the same and a missing smd can't be counted
+    let qc_data = r#"$sequence "a" "missing.smd" {
+frame 0 9
+}
+$sequence "b" "missing.smd" {
+}"#
+    .lines()
+    .map(|line| line.to_owned());
+    let qc = QC::parse("test.qc", qc_data).unwrap();
+    assert_eq!(qc.frame_count("a", &relocated), Some(10));
+    assert_eq!(qc.frame_count("b", &relocated), None);
+}
@@ -0,0 +1,243 @@
+use qcmerger_rust::qc::options::{Blend, LocalHierarchy, OptionOverride, SequenceOptions};
+use qcmerger_rust::qc::QC;
+
+#[test]
+fn write_block_unchanged_test() {
+    for qc_file in &[
+        "tests/scout/c_scout_animations.qc",
+        "tests/Pistol/c_scout_pistol.qc",
+    ] {
+        let qc = QC::new(qc_file).unwrap();
+        for seq in qc.sequences.values() {
+            assert_eq!(seq.write_block(), seq.block, "{}", seq.name);
+        }
+    }
+}
+
+#[test]
+fn parse_test() {
+    let options = SequenceOptions::parse(
+        &r#""p_reload.smd"
+activity "ACT_SECONDARY_VM_RELOAD" 1
+fps 26
+loop
+snap
+fadein 0.2
+fadeout 0.3
+node "raised"
+blend "move_x" -1 1
+blendwidth 3
+delta
+hidden
+realtime
+localhierarchy "weapon_bone" "bip_hand_L" range 1 5 20 25
+frames 1 21
+{ event 5004 18 "Weapon_Pistol.ClipIn" }
+ikrule "rhand" touch "weapon_bone"
+// fps 30"#
+            .lines()
+            .map(|line| line.to_owned())
+            .collect::<Vec<String>>(),
+    );
+    assert_eq!(options.fps, Some(26.0));
+    assert!(options.looping && options.snap && options.delta);
+    assert!(options.hidden && options.realtime);
+    assert_eq!(options.fadein, Some(0.2));
+    assert_eq!(options.fadeout, Some(0.3));
+    assert_eq!(options.node, Some("raised".to_owned()));
+    assert_eq!(
+        options.blend,
+        vec![Blend {
+            parameter: "move_x".to_owned(),
+            min: -1.0,
+            max: 1.0
+        }]
+    );
+    assert_eq!(options.blendwidth, Some(3));
+    assert_eq!(
+        options.localhierarchy,
+        vec![LocalHierarchy {
+            bone: "weapon_bone".to_owned(),
+            parent: "bip_hand_L".to_owned(),
+            range: Some([1.0, 5.0, 20.0, 25.0])
+        }]
+    );
+    assert_eq!(options.frames, Some((1, 21)));
+    assert_eq!(options.events[0].frame, 18);
+}
+
+#[test]
+fn write_block_test() {
+    let mut qc = QC::parse(
+        "test.qc",
+        r#"$sequence "p_draw" "p_draw.smd" {
+activity "ACT_SECONDARY_VM_DRAW" 1
+fadein 0.2
+ikrule "rhand" touch "weapon_bone"
+snap
+}
+$sequence "p_idle" "p_idle.smd""#
+            .lines()
+            .map(|line| line.to_owned()),
+    )
+    .unwrap();
+
+    // changed options are written where they were, new ones at the end,
+    // options without a field stay as they are
+    let p_draw = qc.sequences.get_mut("p_draw").unwrap();
+    p_draw.options.fadein = Some(0.1);
+    p_draw.options.snap = false;
+    p_draw.options.node = Some("raised".to_owned());
+    assert_eq!(
+        p_draw.write_block(),
+        vec![
+            r#"$sequence "p_draw" "p_draw.smd" {"#,
+            r#"activity "ACT_SECONDARY_VM_DRAW" 1"#,
+            "fadein 0.1",
+            r#"ikrule "rhand" touch "weapon_bone""#,
+            r#"node "raised""#,
+            "}",
+        ]
+    );
+
+    // a single-line $sequence gets braces once it has options
+    let p_idle = qc.sequences.get_mut("p_idle").unwrap();
+    assert_eq!(p_idle.write_block(), p_idle.block);
+    p_idle.options.looping = true;
+    assert_eq!(
+        p_idle.write_block(),
+        vec![r#"$sequence "p_idle" "p_idle.smd" {"#, "loop", "}"]
+    );
+}
+
+#[test]
+fn write_block_kept_test() {
+    let mut qc = QC::parse(
+        "test.qc",
+        [
+            r#"$sequence "p_reload" "p_reload.smd" {"#,
+            "\tfps 30 // slowed down",
+            "\t{",
+            "\t\tevent 5004 18 \"Weapon_Pistol.ClipIn\"",
+            "\t}",
+            "\tfadein 0.2",
+            "}",
+        ]
+        .iter()
+        .map(|line| line.to_string()),
+    )
+    .unwrap();
+    let p_reload = &qc.sequences["p_reload"];
+    assert_eq!(p_reload.options.fps, Some(30.0));
+    assert_eq!(p_reload.options.events[0].frame, 18);
+    assert_eq!(p_reload.write_block(), p_reload.block);
+
+    // only the changed options are written again, with their indent and comment
+    qc.set_fps("p_reload", 24.0).unwrap();
+    let p_reload = qc.sequences.get_mut("p_reload").unwrap();
+    assert_eq!(p_reload.block[1], "\tfps 24 // slowed down");
+    assert_eq!(p_reload.options.fps, Some(24.0));
+    assert_eq!(
+        p_reload.block[2..5],
+        ["\t{", "\t\tevent 5004 18 \"Weapon_Pistol.ClipIn\"", "\t}"]
+    );
+    p_reload.options.events[0].frame = 20;
+    assert_eq!(
+        p_reload.write_block()[2..],
+        [
+            "\t{ event 5004 20 \"Weapon_Pistol.ClipIn\" }",
+            "\tfadein 0.2",
+            "}"
+        ]
+    );
+}
+
+#[test]
+fn override_parse_test() {
+    assert_eq!(
+        OptionOverride::parse("FPS=24").unwrap(),
+        OptionOverride {
+            key: "fps".to_owned(),
+            value: Some("24".to_owned())
+        }
+    );
+    assert_eq!(OptionOverride::parse("node=base").unwrap().value, None);
+    assert!(OptionOverride::parse("fps").is_err());
+    assert!(OptionOverride::parse("fps=").is_err());
+    assert!(OptionOverride::parse("ikrule=base").is_err());
+}
+
+#[test]
+fn override_options_test() {
+    let qc_base = QC::new("tests/scout/c_scout_animations.qc").unwrap();
+    let mut qc_data = r#"$sequence "p_draw" "p_draw.smd" {
+fps 30
+activity "ACT_SECONDARY_VM_DRAW" 1
+snap
+{ event 5004 1 "Weapon_Pistol.Draw" }
+}"#
+    .lines()
+    .map(|line| line.to_owned())
+    .collect::<Vec<String>>();
+    let mut qc = QC::parse("test.qc", qc_data.clone().into_iter()).unwrap();
+    let overrides = ["fps=24", "fadein=base", "fadeout=base", "snap=false"]
+        .iter()
+        .map(|option| OptionOverride::parse(option).unwrap())
+        .collect::<Vec<OptionOverride>>();
+    qc.override_options(
+        "p_draw",
+        &overrides,
+        Some(&qc_base.sequences["p_draw"].options),
+    )
+    .unwrap();
+    qc_data = qc.document.lines();
+    assert_eq!(
+        qc_data,
+        vec![
+            r#"$sequence "p_draw" "p_draw.smd" {"#,
+            "fps 24",
+            r#"activity "ACT_SECONDARY_VM_DRAW" 1"#,
+            r#"{ event 5004 1 "Weapon_Pistol.Draw" }"#,
+            "fadein 0.2",
+            "fadeout 0.2",
+            "}",
+        ]
+    );
+    assert_eq!(qc.sequences["p_draw"].options.fadein, Some(0.2));
+
+    // `base` needs a base sequence, and values must fit the option
+    let mut options = SequenceOptions::default();
+    assert!(options.apply(&overrides[1], None).is_err());
+    let frames = OptionOverride::parse("frames=0:20").unwrap();
+    options.apply(&frames, None).unwrap();
+    assert_eq!(options.frames, Some((0, 20)));
+    let looping = OptionOverride::parse("loop=24").unwrap();
+    assert!(options.apply(&looping, None).is_err());
+    assert!(!options.looping);
+}
+
+#[test]
+fn write_block_indent_test() {
+    let qc_file = "tests/scout/c_scout_animations.qc";
+    let file_lines = std::fs::read_to_string(qc_file)
+        .unwrap()
+        .lines()
+        .map(|line| line.to_owned())
+        .collect::<Vec<String>>();
+    let mut qc = QC::new(qc_file).unwrap();
+    let start = file_lines
+        .iter()
+        .position(|line| line == r#"$sequence "p_reload" {"#)
+        .unwrap();
+    let p_reload = &qc.sequences["p_reload"];
+    assert_eq!(p_reload.block[..], file_lines[start..start + 10]);
+    assert_eq!(p_reload.write_block(), p_reload.block);
+
+    // a changed option keeps its tab, an added one gets the tab of the others
+    qc.set_fps("p_reload", 24.0).unwrap();
+    let p_reload = qc.sequences.get_mut("p_reload").unwrap();
+    p_reload.options.looping = true;
+    let block = p_reload.write_block();
+    assert_eq!(block[..7], file_lines[start..start + 7]);
+    assert_eq!(block[7..], ["\tfps 24", "\tframes 89 125", "\tloop", "}"]);
+}
@@ -0,0 +1,293 @@
+use qcmerger_rust::output::{
+    backup_file, backups, copy_donor_smds, copy_smds_in_place, find_duplicates, hash_file,
+    prune_backups, relocate_collisions, relocation_path, restore, same_qc, unreferenced_smds,
+    write_qc, Staging,
+};
+use qcmerger_rust::qc::{path, QC};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+fn output_dir(name: &str) -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let _ = fs::remove_dir_all(&dir);
+    for backup in backups(&dir).unwrap() {
+        fs::remove_dir_all(backup).unwrap();
+    }
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("old.smd"), "old").unwrap();
+    fs::write(dir.join("base.qc"), "old").unwrap();
+    dir
+}
+
+#[test]
+fn staging_replace_test() {
+    let output = output_dir("staging_replace");
+    let staging = Staging::new(&output).unwrap();
+    fs::write(staging.path().join("base.qc"), "new").unwrap();
+    // nothing is touched until the staging folder is swapped in
+    assert_eq!(fs::read_to_string(output.join("base.qc")).unwrap(), "old");
+
+    let staging_path = staging.path().to_path_buf();
+    let backup = staging.replace().unwrap().unwrap();
+    assert_eq!(fs::read_to_string(output.join("base.qc")).unwrap(), "new");
+    assert!(!output.join("old.smd").exists());
+    assert!(!staging_path.exists());
+
+    // the old output is kept as a backup
+    assert_eq!(backups(&output).unwrap(), vec![backup.clone()]);
+    assert_eq!(fs::read_to_string(backup.join("base.qc")).unwrap(), "old");
+}
+
+#[test]
+fn staging_merge_test() {
+    let output = output_dir("staging_merge");
+    let staging = Staging::new(&output).unwrap();
+    fs::create_dir_all(staging.path().join("anims")).unwrap();
+    fs::write(staging.path().join("anims").join("new.smd"), "new").unwrap();
+    fs::write(staging.path().join("base.qc"), "new").unwrap();
+    let backup = staging.merge().unwrap().unwrap();
+    assert_eq!(fs::read_to_string(backup.join("base.qc")).unwrap(), "old");
+
+    assert_eq!(fs::read_to_string(output.join("base.qc")).unwrap(), "new");
+    assert_eq!(fs::read_to_string(output.join("old.smd")).unwrap(), "old");
+    assert!(output.join("anims").join("new.smd").exists());
+}
+
+#[test]
+fn staging_drop_test() {
+    let output = output_dir("staging_drop");
+    let staging = Staging::new(&output).unwrap();
+    let staging_path = staging.path().to_path_buf();
+    fs::write(staging.path().join("base.qc"), "new").unwrap();
+    drop(staging);
+
+    assert!(!staging_path.exists());
+    assert_eq!(fs::read_to_string(output.join("base.qc")).unwrap(), "old");
+}
+
+#[test]
+fn keep_backups_test() {
+    let output = output_dir("keep_backups");
+    // nothing changed, so nothing is backed up
+    let staging = Staging::new(&output).unwrap();
+    fs::write(staging.path().join("old.smd"), "old").unwrap();
+    fs::write(staging.path().join("base.qc"), "old").unwrap();
+    assert!(staging.replace().unwrap().is_none());
+    let staging = Staging::new(&output).unwrap();
+    fs::write(staging.path().join("base.qc"), "old").unwrap();
+    assert!(staging.merge().unwrap().is_none());
+    assert!(backups(&output).unwrap().is_empty());
+
+    for i in 0..3 {
+        let staging = Staging::new(&output).unwrap();
+        fs::write(staging.path().join("base.qc"), i.to_string()).unwrap();
+        staging.replace().unwrap().unwrap();
+    }
+    let all = backups(&output).unwrap();
+    assert_eq!(all.len(), 3);
+    assert_eq!(prune_backups(&output, 2).unwrap(), all[..1]);
+    assert_eq!(backups(&output).unwrap(), all[1..]);
+
+    // the base qc's backups for --in-place
+    let base_qc = output.join("base.qc");
+    write_qc(&base_qc, &["old".to_owned()]).unwrap();
+    assert!(same_qc(&base_qc, &["old".to_owned()]));
+    assert!(!same_qc(&base_qc, &["new".to_owned()]));
+    let first = backup_file(&base_qc).unwrap();
+    let second = backup_file(&base_qc).unwrap();
+    assert_eq!(prune_backups(&base_qc, 1).unwrap(), [first]);
+    assert!(second.exists());
+    assert_eq!(backups(&output).unwrap().len(), 2);
+}
+
+#[test]
+fn backup_order_test() {
+    let output = output_dir("backup_order");
+    let name = |n: u32| match n {
+        1 => "backup_order.backup-2021-07-04_13-05-59".to_owned(),
+        n => format!("backup_order.backup-2021-07-04_13-05-59-{}", n),
+    };
+    for n in (1..=11).rev() {
+        fs::create_dir(output.with_file_name(name(n))).unwrap();
+    }
+    fs::create_dir(output.with_file_name("backup_order.backup-2021-07-04_13-06-00")).unwrap();
+
+    // `-10` is newer than `-2`
+    let all = backups(&output).unwrap();
+    let names = all
+        .iter()
+        .map(|backup| backup.file_name().unwrap().to_string_lossy().into_owned())
+        .collect::<Vec<String>>();
+    assert_eq!(names[..11], (1..=11).map(name).collect::<Vec<String>>()[..]);
+    assert_eq!(names[11], "backup_order.backup-2021-07-04_13-06-00");
+    assert_eq!(prune_backups(&output, 2).unwrap(), all[..10]);
+    assert_eq!(backups(&output).unwrap(), all[10..]);
+}
+
+#[test]
+fn restore_test() {
+    let output = output_dir("restore");
+    let staging = Staging::new(&output).unwrap();
+    fs::write(staging.path().join("base.qc"), "new").unwrap();
+    let backup = staging.replace().unwrap().unwrap();
+
+    let current = restore(&output, &backup).unwrap().unwrap();
+    assert_eq!(fs::read_to_string(output.join("base.qc")).unwrap(), "old");
+    assert_eq!(fs::read_to_string(current.join("base.qc")).unwrap(), "new");
+    // restoring copies the backup, it can be restored again
+    assert!(backup.exists());
+    assert_eq!(backups(&output).unwrap().len(), 2);
+}
+
+#[test]
+fn in_place_test() {
+    let base = output_dir("in_place");
+    let base_qc = base.join("base.qc");
+    let donor_qc = PathBuf::from("tests/Pistol/c_scout_pistol.qc");
+
+    // p_draw.smd would overwrite an smd the base qc uses
+    let base_smds = vec![PathBuf::from("p_draw.smd")];
+    let new_smds = vec![PathBuf::from("p_draw.smd"), PathBuf::from("p_fire.smd")];
+    assert!(
+        copy_smds_in_place(&new_smds, &HashMap::new(), &donor_qc, &base_qc, &base_smds).is_err()
+    );
+    assert!(!base.join("p_fire.smd").exists());
+
+    let copied = copy_smds_in_place(
+        &new_smds[1..],
+        &HashMap::new(),
+        &donor_qc,
+        &base_qc,
+        &base_smds,
+    )
+    .unwrap();
+    assert_eq!(copied, new_smds[1..]);
+    assert!(base.join("p_fire.smd").exists());
+
+    // old.smd isn't used by the base qc, but it's still a different file
+    let donor = output_dir("in_place_donor");
+    fs::write(donor.join("old.smd"), "donor").unwrap();
+    let old_smds = vec![PathBuf::from("old.smd")];
+    let donor_qc = donor.join("base.qc");
+    assert!(copy_smds_in_place(&old_smds, &HashMap::new(), &donor_qc, &base_qc, &[]).is_err());
+    assert_eq!(fs::read_to_string(base.join("old.smd")).unwrap(), "old");
+    // identical files are skipped, they aren't the merge's copies
+    fs::write(donor.join("old.smd"), "old").unwrap();
+    let copied = copy_smds_in_place(&old_smds, &HashMap::new(), &donor_qc, &base_qc, &[]).unwrap();
+    assert!(copied.is_empty());
+
+    let backup = backup_file(&base_qc).unwrap();
+    write_qc(&base_qc, &["new".to_owned()]).unwrap();
+    assert_eq!(fs::read_to_string(&base_qc).unwrap(), "new\n");
+    assert_eq!(fs::read_to_string(backup).unwrap(), "old");
+}
+
+#[test]
+fn relocate_collisions_test() {
+    let base = output_dir("relocate_base");
+    let donor = output_dir("relocate_donor");
+    let qc = r#"$sequence "p_draw" "p_draw.smd" {
+}
+$sequence "p_idle" "p_idle.smd" {
+}"#;
+    fs::write(base.join("base.qc"), qc).unwrap();
+    fs::write(donor.join("donor.qc"), qc).unwrap();
+    fs::write(base.join("p_draw.smd"), "base").unwrap();
+    fs::write(donor.join("p_draw.smd"), "donor").unwrap();
+    fs::write(base.join("p_idle.smd"), "same").unwrap();
+    fs::write(donor.join("p_idle.smd"), "same").unwrap();
+
+    let base_qc = QC::new(base.join("base.qc")).unwrap();
+    let mut donor_qc = QC::new(donor.join("donor.qc")).unwrap();
+    let kept = base_qc.get_smds();
+    let relocated =
+        relocate_collisions(&mut donor_qc, &base_qc, |smd| path::contains(&kept, smd)).unwrap();
+
+    // only p_draw.smd has different content
+    assert_eq!(relocated.len(), 1);
+    assert_eq!(
+        relocated[&PathBuf::from("donor/p_draw.smd")],
+        PathBuf::from("p_draw.smd")
+    );
+    assert_eq!(donor_qc.sequences["p_draw"].smd, "donor/p_draw.smd");
+    assert_eq!(
+        donor_qc.sequences["p_draw"].block[0],
+        r#"$sequence "p_draw" "donor/p_draw.smd" {"#
+    );
+    assert_eq!(donor_qc.sequences["p_idle"].smd, "p_idle.smd");
+    assert_eq!(
+        relocation_path("donor", r"anims\p_draw.smd"),
+        r"donor\anims\p_draw.smd"
+    );
+
+    let output = output_dir("relocate_output");
+    copy_donor_smds(
+        &[PathBuf::from("donor/p_draw.smd")],
+        &relocated,
+        donor.join("donor.qc"),
+        &output,
+    )
+    .unwrap();
+    assert_eq!(
+        fs::read_to_string(output.join("donor").join("p_draw.smd")).unwrap(),
+        "donor"
+    );
+
+    // the base's p_draw.smd isn't used once p_draw is replaced
+    let mut donor_qc = QC::new(donor.join("donor.qc")).unwrap();
+    let kept = base_qc.get_smds_except(&["p_draw".to_owned()]);
+    assert_eq!(kept, [PathBuf::from("p_idle.smd")]);
+    let relocated =
+        relocate_collisions(&mut donor_qc, &base_qc, |smd| path::contains(&kept, smd)).unwrap();
+    assert!(relocated.is_empty());
+    assert_eq!(donor_qc.sequences["p_draw"].smd, "p_draw.smd");
+}
+
+#[test]
+fn find_duplicates_test() {
+    let dir = output_dir("duplicates");
+    fs::write(dir.join("a.smd"), "same").unwrap();
+    fs::write(dir.join("b.smd"), "same").unwrap();
+    fs::write(dir.join("c.smd"), "different").unwrap();
+    fs::write(dir.join("d.smd"), "different").unwrap();
+    fs::write(dir.join("e.smd"), "same").unwrap();
+    assert_eq!(
+        hash_file(dir.join("a.smd")).unwrap(),
+        hash_file(dir.join("b.smd")).unwrap()
+    );
+
+    let smds = ["a.smd", "a.smd", "b.smd", "c.smd", "e.smd", "d.smd"]
+        .iter()
+        .map(|smd| (PathBuf::from(smd), dir.join(smd)))
+        .collect::<Vec<(PathBuf, PathBuf)>>();
+    let duplicates = find_duplicates(&smds).unwrap();
+    assert_eq!(duplicates.len(), 3);
+    assert_eq!(duplicates[&PathBuf::from("b.smd")], PathBuf::from("a.smd"));
+    // in the order of smds, so the messages are the same every run
+    assert_eq!(
+        duplicates.keys().collect::<Vec<&PathBuf>>(),
+        [
+            &PathBuf::from("b.smd"),
+            &PathBuf::from("e.smd"),
+            &PathBuf::from("d.smd")
+        ]
+    );
+}
+
+#[test]
+fn unreferenced_smds_test() {
+    let dir = output_dir("unreferenced");
+    fs::create_dir_all(dir.join("anims")).unwrap();
+    fs::write(dir.join("anims").join("p_draw.smd"), "").unwrap();
+    fs::write(dir.join("anims").join("p_idle.SMD"), "").unwrap();
+
+    let referenced = vec![PathBuf::from("anims").join("p_draw.smd")];
+    assert_eq!(
+        unreferenced_smds(&dir, &referenced).unwrap(),
+        vec![
+            PathBuf::from("anims").join("p_idle.SMD"),
+            PathBuf::from("old.smd")
+        ]
+    );
+}
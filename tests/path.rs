@@ -0,0 +1,82 @@
+use qcmerger_rust::qc::path::{
+    candidates, contains, key, normalize, output_path, relative_to, resolve,
+};
+use std::path::PathBuf;
+
+#[test]
+fn normalize_test() {
+    assert_eq!(
+        normalize(r"c_scout_animations_anims\b_idle.smd"),
+        PathBuf::from("c_scout_animations_anims").join("b_idle.smd")
+    );
+    assert_eq!(
+        normalize(r".\anims//b_idle.smd"),
+        PathBuf::from("anims").join("b_idle.smd")
+    );
+}
+
+#[test]
+fn key_test() {
+    assert_eq!(key(r"Anims\P_Draw.SMD"), key("anims/p_draw.smd"));
+    assert_ne!(key("anims/p_draw.smd"), key("p_draw.smd"));
+    let smds = vec![PathBuf::from(r"anims\p_draw.smd")];
+    assert!(contains(&smds, "Anims/P_Draw.smd"));
+    assert!(!contains(&smds, "anims/p_idle.smd"));
+}
+
+#[test]
+fn resolve_test() {
+    let expected = PathBuf::from("tests/scout/c_scout_animations_anims/b_idle.smd");
+    assert_eq!(
+        resolve("tests/scout", r"c_scout_animations_anims\b_idle.smd"),
+        expected
+    );
+    // ignores case and adds the extension
+    assert_eq!(
+        resolve("tests/scout", r"C_SCOUT_ANIMATIONS_ANIMS\B_Idle"),
+        expected
+    );
+    // can't be found, still normalized
+    assert_eq!(
+        resolve("tests/scout", r"anims\missing.smd"),
+        PathBuf::from("tests/scout/anims/missing.smd")
+    );
+}
+
+#[test]
+fn output_path_test() {
+    assert_eq!(
+        output_path(r"Anims\P_Draw", "tests/Pistol/p_draw.smd"),
+        PathBuf::from("Anims").join("P_Draw.smd")
+    );
+    assert_eq!(
+        output_path(r"anims\p_draw.smd", "tests/Pistol/p_draw.smd"),
+        PathBuf::from("anims").join("p_draw.smd")
+    );
+}
+
+#[test]
+fn candidates_test() {
+    assert_eq!(
+        candidates("qc", r"anims\p_draw"),
+        vec![
+            PathBuf::from("qc/anims/p_draw"),
+            PathBuf::from("qc/anims/p_draw.smd"),
+            PathBuf::from("qc/anims/p_draw.dmx")
+        ]
+    );
+    assert_eq!(
+        candidates("qc", "p_draw.smd"),
+        vec![PathBuf::from("qc/p_draw.smd")]
+    );
+}
+
+#[test]
+fn relative_to_test() {
+    assert_eq!(relative_to("", "anims/p_draw.smd"), "anims/p_draw.smd");
+    assert_eq!(relative_to("anims", "anims/p_draw.smd"), "p_draw.smd");
+    assert_eq!(
+        relative_to("anims/pistol", r"other\p_draw.smd"),
+        r"..\..\other\p_draw.smd"
+    );
+}
@@ -0,0 +1,262 @@
+use qcmerger_rust::qc::placement::Placement;
+use qcmerger_rust::qc::QC;
+
+#[test]
+fn parse_test() {
+    assert_eq!("kind".parse::<Placement>().unwrap(), Placement::Kind);
+    assert_eq!("end".parse::<Placement>().unwrap(), Placement::End);
+    assert_eq!("group".parse::<Placement>().unwrap(), Placement::Group);
+    assert_eq!("qci".parse::<Placement>().unwrap(), Placement::Qci);
+    assert_eq!(
+        "anchor:p_idle".parse::<Placement>().unwrap(),
+        Placement::Anchor("p_idle".to_owned())
+    );
+    assert!("anchor:".parse::<Placement>().is_err());
+    assert!("top".parse::<Placement>().is_err());
+}
+
+#[test]
+fn kind_test() {
+    let mut qc = QC::parse(
+        "base.qc",
+        r#"$weightlist "base_weights" {
+"root" 1
+}
+$pushd "anims"
+$animation "base_anim" "base_anim.smd" {
+}
+$sequence "p_idle" "p_idle.smd" {
+}
+$popd
+$sequence "p_draw" "p_draw.smd" {
+}"#
+        .lines()
+        .map(|line| line.to_owned()),
+    )
+    .unwrap();
+    let donor = QC::parse(
+        "donors/donor.qc",
+        r#"$weightlist "donor_weights" {
+"root" 0
+}
+$animation "donor_anim" "donor_anim.smd" {
+weightlist "donor_weights"
+}
+$sequence "p_new" "donor_anim" {
+}"#
+        .lines()
+        .map(|line| line.to_owned()),
+    )
+    .unwrap();
+    // in the order main appends them
+    qc.append_sequence(&donor, "p_new").unwrap();
+    qc.append_weightlist(&donor, "donor_weights").unwrap();
+    qc.append_animation(&donor, "donor_anim").unwrap();
+
+    let (qc_lines, qci_lines) = qc.output_lines();
+    assert!(qci_lines.is_none());
+    assert_eq!(qc_lines[0], r#"$weightlist "donor_weights" {"#);
+    assert_eq!(qc_lines[7], r#"$animation "donor_anim" "donor_anim.smd" {"#);
+    assert_eq!(qc_lines[17], r#"$sequence "p_new" "donor_anim" {"#);
+    assert_eq!(qc.sequences["p_new"].dir, "");
+    assert_eq!(qc.animations["donor_anim"].dir, "anims");
+}
+
+#[test]
+fn end_test() {
+    let mut qc = QC::parse(
+        "base.qc",
+        r#"$weightlist "base_weights" {
+"root" 1
+}
+$pushd "anims"
+$sequence "p_idle" "p_idle.smd" {
+}
+$popd"#
+            .lines()
+            .map(|line| line.to_owned()),
+    )
+    .unwrap();
+    qc.placement = Placement::End;
+    let donor_data = r#"$weightlist "donor_weights" {
+"root" 0
+}
+$animation "donor_anim" "donor_anim.smd" {
+weightlist "donor_weights"
+}
+$sequence "p_new" "donor_anim" {
+}"#;
+    let donor = QC::parse("donor.qc", donor_data.lines().map(|line| line.to_owned())).unwrap();
+    qc.append_sequence(&donor, "p_new").unwrap();
+    qc.append_weightlist(&donor, "donor_weights").unwrap();
+    qc.append_animation(&donor, "donor_anim").unwrap();
+
+    // the $weightlists and $animations still come before the $sequences that use them
+    let (qc_lines, _) = qc.output_lines();
+    assert_eq!(qc_lines[7..], donor_data.lines().collect::<Vec<&str>>()[..]);
+    assert!(qc.sequences["p_new"].uses_animation);
+    assert_eq!(qc.sequences["p_new"].dir, "");
+}
+
+#[test]
+fn group_test() {
+    let mut qc = QC::parse(
+        "base.qc",
+        r#"$sequence "p_idle" "p_idle.smd" {
+}"#
+        .lines()
+        .map(|line| line.to_owned()),
+    )
+    .unwrap();
+    qc.placement = Placement::Group;
+    let donor_data = r#"$animation "donor_anim" "donor_anim.smd" {
+}
+$sequence "p_new" "donor_anim" {
+}"#;
+    let donor = QC::parse(
+        "donors/donor.qc",
+        donor_data.lines().map(|line| line.to_owned()),
+    )
+    .unwrap();
+    qc.append_sequence(&donor, "p_new").unwrap();
+    qc.append_animation(&donor, "donor_anim").unwrap();
+
+    let (qc_lines, _) = qc.output_lines();
+    assert_eq!(qc_lines[2], "// merged from donor.qc");
+    assert_eq!(qc_lines[3..], donor_data.lines().collect::<Vec<&str>>()[..]);
+}
+
+#[test]
+fn anchor_test() {
+    let mut qc = QC::parse(
+        "base.qc",
+        r#"$pushd "anims"
+$sequence "p_idle" "p_idle.smd" {
+}
+$popd
+$sequence "p_draw" "p_draw.smd" {
+}"#
+        .lines()
+        .map(|line| line.to_owned()),
+    )
+    .unwrap();
+    qc.placement = Placement::Anchor("p_idle".to_owned());
+    let donor_data = r#"$animation "donor_anim" "donor_anim.smd" {
+}
+$sequence "p_new" "donor_anim" {
+}"#;
+    let donor = QC::parse("donor.qc", donor_data.lines().map(|line| line.to_owned())).unwrap();
+    qc.append_sequence(&donor, "p_new").unwrap();
+    qc.append_animation(&donor, "donor_anim").unwrap();
+
+    let (qc_lines, _) = qc.output_lines();
+    assert_eq!(
+        qc_lines[3..7],
+        donor_data.lines().collect::<Vec<&str>>()[..]
+    );
+    assert_eq!(qc_lines[7], "$popd");
+    assert_eq!(qc.sequences["p_new"].dir, "anims");
+
+    // the anchor has to be in the qc
+    let mut qc = QC::parse("base.qc", qc.document.lines().into_iter()).unwrap();
+    qc.placement = Placement::Anchor("p_reload".to_owned());
+    let donor = QC::parse(
+        "donor.qc",
+        "$sequence \"p_other\" {\n}"
+            .lines()
+            .map(|line| line.to_owned()),
+    )
+    .unwrap();
+    assert!(qc.append_sequence(&donor, "p_other").is_err());
+}
+
+#[test]
+fn qci_test() {
+    let base_data = r#"$sequence "p_idle" "p_idle.smd" {
+}"#;
+    let mut qc = QC::parse("base.qc", base_data.lines().map(|line| line.to_owned())).unwrap();
+    qc.placement = Placement::Qci;
+    let donor_data = r#"$sequence "p_new" "p_new.smd" {
+}"#;
+    let donor = QC::parse(
+        "donors/donor.qc",
+        donor_data.lines().map(|line| line.to_owned()),
+    )
+    .unwrap();
+    qc.append_sequence(&donor, "p_new").unwrap();
+
+    assert_eq!(qc.qci_name(), "base_merged.qci");
+    let (qc_lines, qci_lines) = qc.output_lines();
+    assert_eq!(
+        qc_lines,
+        [
+            r#"$sequence "p_idle" "p_idle.smd" {"#,
+            "}",
+            r#"$include "base_merged.qci""#
+        ]
+    );
+    let qci_lines = qci_lines.unwrap();
+    assert_eq!(qci_lines[0], "// merged from donor.qc");
+    assert_eq!(
+        qci_lines[1..],
+        donor_data.lines().collect::<Vec<&str>>()[..]
+    );
+
+    // nothing was appended, so there's no .qci
+    let qc = QC::parse("base.qc", base_data.lines().map(|line| line.to_owned())).unwrap();
+    assert!(qc.output_lines().1.is_none());
+
+    // an earlier merge's .qci isn't replaced or $included twice
+    let mut qc = QC::parse("base.qc", qc_lines.into_iter()).unwrap();
+    qc.placement = Placement::Qci;
+    assert!(qc.check_qci().is_err());
+    assert!(qc.append_sequence(&donor, "p_new").is_err());
+    qc.placement = Placement::End;
+    assert!(qc.append_sequence(&donor, "p_new").is_ok());
+}
+
+#[test]
+fn qci_replace_test() {
+    let mut qc = QC::parse(
+        "base.qc",
+        r#"$sequence "p_idle" "p_idle.smd" {
+}"#
+        .lines()
+        .map(|line| line.to_owned()),
+    )
+    .unwrap();
+    qc.placement = Placement::Qci;
+    let donor = QC::parse(
+        "donors/donor.qc",
+        r#"$sequence "p_idle" "p_idle_new.smd" {
+loop
+}
+$sequence "p_new" "p_new.smd" {
+}"#
+        .lines()
+        .map(|line| line.to_owned()),
+    )
+    .unwrap();
+    qc.replace_sequence(&donor, "p_idle").unwrap();
+    qc.append_sequence(&donor, "p_new").unwrap();
+
+    // the replaced sequence is changed in the base qc, only the appended one is in the qci
+    let (qc_lines, qci_lines) = qc.output_lines();
+    assert_eq!(
+        qc_lines,
+        [
+            r#"$sequence "p_idle" "p_idle_new.smd" {"#,
+            "loop",
+            "}",
+            r#"$include "base_merged.qci""#
+        ]
+    );
+    assert_eq!(
+        qci_lines.unwrap(),
+        [
+            "// merged from donor.qc",
+            r#"$sequence "p_new" "p_new.smd" {"#,
+            "}"
+        ]
+    );
+}
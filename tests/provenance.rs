@@ -0,0 +1,92 @@
+use qcmerger_rust::qc::QC;
+
+#[test]
+fn provenance_test() {
+    let mut qc = QC::parse(
+        "base.qc",
+        r#"$animation "base_anim" "base_anim.smd" {
+}
+$sequence "p_idle" "p_idle.smd" {
+}"#
+        .lines()
+        .map(|line| line.to_owned()),
+    )
+    .unwrap();
+    qc.provenance = Some("recipe".to_owned());
+    let donor = QC::parse(
+        "donor.qc",
+        r#"$sequence "p_idle" "p_idle_new.smd" {
+fps 30
+}
+$animation "first_anim" "first_anim.smd" {
+}
+$animation "second_anim" "second_anim.smd" {
+}
+$sequence "p_new" "first_anim" {
+}"#
+        .lines()
+        .map(|line| line.to_owned()),
+    )
+    .unwrap();
+    qc.replace_sequence(&donor, "p_idle").unwrap();
+    qc.append_sequence(&donor, "p_new").unwrap();
+    qc.append_animation(&donor, "first_anim").unwrap();
+    qc.append_animation(&donor, "second_anim").unwrap();
+    // the comment stays when the block is changed after the merge, and above its own block
+    // when another animation is appended before it
+    qc.set_fps("p_new", 24.0).unwrap();
+    assert_eq!(
+        qc.document.lines(),
+        r#"// appended from donor.qc lines 6-7, recipe
+$animation "second_anim" "second_anim.smd" {
+}
+// appended from donor.qc lines 4-5, recipe
+$animation "first_anim" "first_anim.smd" {
+}
+$animation "base_anim" "base_anim.smd" {
+}
+// replaced from donor.qc lines 1-3, recipe
+$sequence "p_idle" "p_idle_new.smd" {
+fps 30
+}
+// appended from donor.qc lines 8-9, recipe
+$sequence "p_new" "first_anim" {
+fps 24
+}"#
+        .lines()
+        .collect::<Vec<&str>>()
+    );
+}
+
+#[test]
+fn no_provenance_test() {
+    let mut qc = QC::parse(
+        "base.qc",
+        r#"$sequence "p_idle" "p_idle.smd" {
+}"#
+        .lines()
+        .map(|line| line.to_owned()),
+    )
+    .unwrap();
+    let donor = QC::parse(
+        "donor.qc",
+        r#"$sequence "p_idle" "p_idle_new.smd" {
+}
+$sequence "p_new" "p_new.smd" {
+}"#
+        .lines()
+        .map(|line| line.to_owned()),
+    )
+    .unwrap();
+    qc.replace_sequence(&donor, "p_idle").unwrap();
+    qc.append_sequence(&donor, "p_new").unwrap();
+    assert_eq!(
+        qc.document.lines(),
+        [
+            r#"$sequence "p_idle" "p_idle_new.smd" {"#,
+            "}",
+            r#"$sequence "p_new" "p_new.smd" {"#,
+            "}"
+        ]
+    );
+}
@@ -3,7 +3,7 @@ use std::path::PathBuf;
 
 #[test]
 fn parse_test() {
-    let qc_file = r"C:\Users\mrmon\PycharmProjects\qcmerger-rust\tests\scout\c_scout_animations.qc";
+    let qc_file = "tests/scout/c_scout_animations.qc";
     let x = qc::QC::new(qc_file).unwrap();
     assert_eq!(x.qc_file, PathBuf::from(qc_file));
 
@@ -38,13 +38,16 @@ fn parse_test() {
     assert_eq!(&test_layer_anim.start, &1204usize);
     assert_eq!(&test_layer_anim.end, &1204usize);
 
+    assert_eq!(x.bones.len(), 58);
+    assert_eq!(&x.bones[0], "root");
+
     let weights_r_handposes = &x.weightlists["weights_r_handposes"];
     assert_eq!(weights_r_handposes.name, "weights_r_handposes");
 }
 
 #[test]
 fn parse_test_sca() {
-    let qc_file = r"C:\Users\mrmon\PycharmProjects\qcmerger-rust\tests\Pistol\c_scout_pistol.qc";
+    let qc_file = "tests/Pistol/c_scout_pistol.qc";
     let x = qc::QC::new(qc_file).unwrap();
     assert_eq!(x.qc_file, PathBuf::from(qc_file));
 
@@ -60,3 +63,268 @@ fn parse_test_sca() {
 fn parse_test_fail() {
     let _ = qc::QC::new(r"non-existent file").unwrap();
 }
+
+#[test]
+fn merge_status_test() {
+    use qc::sequence::MergeStatus;
+
+    let base = qc::QC::parse(
+        "base.qc",
+        r#"$animation "p_fire" "p_fire.smd"
+$sequence "p_draw" "p_draw.smd" {
+activity "ACT_SECONDARY_VM_DRAW" 1
+}
+$sequence "p_reload" "p_reload.smd" {
+activity "ACT_SECONDARY_VM_RELOAD" 1
+}"#
+        .lines()
+        .map(|l| l.to_owned()),
+    )
+    .unwrap();
+    let other = qc::QC::new("tests/Pistol/c_scout_pistol.qc").unwrap();
+
+    assert_eq!(base.merge_status(&other, "p_draw"), MergeStatus::Replace);
+    assert_eq!(base.merge_status(&other, "p_idle"), MergeStatus::Append);
+    assert_eq!(
+        base.merge_status(&other, "p_fire"),
+        MergeStatus::NameCollision
+    );
+
+    let mut other = other;
+    other.sequences.get_mut("p_reload").unwrap().activity = "ACT_VM_RELOAD".to_owned();
+    assert_eq!(
+        base.merge_status(&other, "p_reload"),
+        MergeStatus::ActivityMismatch
+    );
+}
+
+#[test]
+fn relocate_smd_in_test() {
+    let qc_data = r#"$sequence "p_draw" "p_draw.smd" {
+activity "ACT_SECONDARY_VM_DRAW" 1
+}
+$animation "p_idle_anim" "p_idle.smd" loop
+$sequence "p_idle" {
+"p_idle.smd"
+}"#
+    .lines()
+    .map(|l| l.to_owned())
+    .collect::<Vec<String>>();
+    let mut x = qc::QC::parse("base.qc", qc_data.into_iter()).unwrap();
+
+    x.relocate_smd_in("p_draw.smd", "shared.smd");
+    x.relocate_smd_in("p_idle.smd", "shared.smd");
+    let y = x.document.lines();
+    assert_eq!(y[0], r#"$sequence "p_draw" "shared.smd" {"#);
+    assert_eq!(y[3], r#"$animation "p_idle_anim" "shared.smd" loop"#);
+    assert_eq!(y[5], r#""shared.smd""#);
+    assert_eq!(x.sequences["p_draw"].smd, "shared.smd");
+    assert_eq!(x.animations["p_idle_anim"].smd, "shared.smd");
+
+    // only the one $sequence moves, the others keep the shared file
+    x.relocate_sequence_smd("p_draw", "donor_30fps/shared.smd");
+    let y = x.document.lines();
+    assert_eq!(y[0], r#"$sequence "p_draw" "donor_30fps/shared.smd" {"#);
+    assert_eq!(y[3], r#"$animation "p_idle_anim" "shared.smd" loop"#);
+    assert_eq!(x.sequences["p_draw"].smd, "donor_30fps/shared.smd");
+}
+
+#[test]
+fn parse_test_studiomdl_references() {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("studiomdl_references");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join("anims")).unwrap();
+    std::fs::write(dir.join("anims").join("p_draw.smd"), "").unwrap();
+    std::fs::write(dir.join("p_reload.dmx"), "").unwrap();
+    std::fs::write(
+        dir.join("test.qc"),
+        r#"$pushd "anims"
+$sequence "p_draw" "p_draw" {
+}
+$popd
+$animation "p_idle_anim" "anims/p_draw.smd" {
+}
+$sequence "p_idle" "p_idle_anim" {
+}
+$sequence "p_reload" "p_reload" {
+}
+$sequence "p_fire" {
+"p_fire.smd"
+}
+$sequence "p_idle_missing" "p_idle_missing" {
+}"#,
+    )
+    .unwrap();
+    let mut x = qc::QC::new(dir.join("test.qc")).unwrap();
+
+    // no extension, but the file exists in the $pushd folder
+    let p_draw = &x.sequences["p_draw"];
+    assert!(!p_draw.uses_animation);
+    assert_eq!(p_draw.dir, "anims");
+    assert_eq!(p_draw.smd_path(), "anims/p_draw");
+    assert!(x.sequences["p_idle"].uses_animation);
+    assert!(!x.sequences["p_reload"].uses_animation);
+
+    let missing = x.missing_smds();
+    assert_eq!(missing.len(), 2);
+    assert_eq!(missing[0].0, "p_fire");
+    assert_eq!(missing[0].2, vec![dir.join("p_fire.smd")]);
+    // neither an $animation nor a file
+    assert_eq!(missing[1].0, "p_idle_missing");
+    assert_eq!(missing[1].2.len(), 3);
+    let donor = x.clone();
+    assert!(x.append_animation(&donor, "p_idle_missing").is_err());
+    assert!(x.replace_animation(&donor, "p_idle_missing").is_err());
+}
+
+#[test]
+fn parse_test_dmx() {
+    let x = qc::QC::parse(
+        "test.qc",
+        r#"$animation "p_idle_anim" {
+"anims/p_idle.DMX"
+}
+$sequence "p_draw" "p_draw.dmx" {
+}
+$sequence "p_fire" {
+"p_fire.dmx"
+}"#
+        .lines()
+        .map(|line| line.to_owned()),
+    )
+    .unwrap();
+
+    // dmx files aren't mistaken for $animation names, even if they can't be found
+    assert!(!x.sequences["p_draw"].uses_animation);
+    assert!(!x.sequences["p_fire"].uses_animation);
+    assert_eq!(&x.animations["p_idle_anim"].smd, "anims/p_idle.DMX");
+    let mut smds = x.get_smds();
+    smds.sort();
+    assert_eq!(
+        smds,
+        vec![
+            PathBuf::from("anims/p_idle.DMX"),
+            PathBuf::from("p_draw.dmx"),
+            PathBuf::from("p_fire.dmx")
+        ]
+    );
+}
+
+#[test]
+fn rebase_smds_test() {
+    let other = qc::QC::parse(
+        "donor.qc",
+        r#"$pushd "anims"
+$sequence "p_draw" "p_draw.smd" {
+}
+$popd"#
+            .lines()
+            .map(|l| l.to_owned()),
+    )
+    .unwrap();
+    let mut y = r#"$pushd "base_anims"
+$sequence "p_draw" "p_draw.smd" {
+}
+$popd"#
+        .lines()
+        .map(|l| l.to_owned())
+        .collect::<Vec<String>>();
+    let mut x = qc::QC::parse("base.qc", y.clone().into_iter()).unwrap();
+
+    x.rebase_smds(&other, &["p_draw".to_owned()]);
+    y = x.document.lines();
+    assert_eq!(y[1], r#"$sequence "p_draw" "../anims/p_draw.smd" {"#);
+    assert_eq!(x.sequences["p_draw"].smd, "../anims/p_draw.smd");
+}
+
+#[test]
+fn document_order_test() {
+    let mut y = r#"$weightlist "weights_b" {
+"root" 0
+}
+$weightlist "weights_a" {
+"root" 1
+}
+$animation "z_anim" "z_anim.smd" {
+}
+$animation "a_anim" "a_anim.smd" {
+}
+$sequence "p_reload" "p_reload.smd" {
+}
+$sequence "p_draw" "z_anim" {
+}
+$sequence "p_idle" "p_idle.smd" {
+}"#
+    .lines()
+    .map(|l| l.to_owned())
+    .collect::<Vec<String>>();
+    let mut x = qc::QC::parse("base.qc", y.clone().into_iter()).unwrap();
+    assert_eq!(
+        x.sequences.keys().collect::<Vec<&String>>(),
+        vec!["p_reload", "p_draw", "p_idle"]
+    );
+    assert_eq!(
+        x.animations.keys().collect::<Vec<&String>>(),
+        vec!["z_anim", "a_anim"]
+    );
+    assert_eq!(
+        x.get_smds(),
+        vec![
+            PathBuf::from("p_reload.smd"),
+            PathBuf::from("p_idle.smd"),
+            PathBuf::from("z_anim.smd"),
+            PathBuf::from("a_anim.smd"),
+        ]
+    );
+
+    // renamed blocks keep their place
+    x.rename_animation("z_anim", "z_anim_donor").unwrap();
+    x.rename_weightlist("weights_b", "weights_b_donor").unwrap();
+    assert_eq!(
+        x.animations.keys().collect::<Vec<&String>>(),
+        vec!["z_anim_donor", "a_anim"]
+    );
+    assert_eq!(
+        x.weightlists.keys().collect::<Vec<&String>>(),
+        vec!["weights_b_donor", "weights_a"]
+    );
+
+    // appended $weightlists always go before the first one
+    let other = qc::QC::parse(
+        "donor.qc",
+        r#"$weightlist "weights_c" {
+"root" 0.5
+}"#
+        .lines()
+        .map(|l| l.to_owned()),
+    )
+    .unwrap();
+    let mut x = qc::QC::parse("base.qc", y.clone().into_iter()).unwrap();
+    x.append_weightlist(&other, "weights_c").unwrap();
+    y = x.document.lines();
+    assert_eq!(y[0], r#"$weightlist "weights_c" {"#);
+    assert_eq!(y[3], r#"$weightlist "weights_b" {"#);
+}
+
+#[test]
+fn unchanged_lines_test() {
+    let qc_file = "tests/scout/c_scout_animations.qc";
+    let text = std::fs::read_to_string(qc_file).unwrap();
+    let mut x = qc::QC::new(qc_file).unwrap();
+    // the lines are written back as they were, indentation included
+    assert!(x.document.lines().iter().eq(text.lines()));
+    assert_eq!(x.sequences["p_draw"].activity, "ACT_SECONDARY_VM_DRAW");
+
+    // only the replaced block changes
+    let y = qc::QC::new("tests/Pistol/c_scout_pistol.qc").unwrap();
+    let (start, end) = (x.sequences["p_draw"].start, x.sequences["p_draw"].end);
+    x.replace_sequence(&y, "p_draw").unwrap();
+    let lines = x.document.lines();
+    let text = text.lines().collect::<Vec<&str>>();
+    assert_eq!(lines[..start], text[..start]);
+    assert_eq!(lines[lines.len() + end + 1 - text.len()..], text[end + 1..]);
+    assert_ne!(
+        lines[start..lines.len() + end + 1 - text.len()],
+        text[start..=end]
+    );
+}
@@ -1,4 +1,6 @@
-use qcmerger_rust::qc::sequence::Sequence;
+use qcmerger_rust::qc::options::Activity;
+use qcmerger_rust::qc::sequence::{MergeStatus, Sequence};
+use qcmerger_rust::qc::QC;
 
 #[test]
 fn parse_test() {
@@ -16,8 +18,8 @@ weightlist "test_weight"
 addlayer "test_layer"
 }"#,
     );
-    let mut y: Vec<String> = x.lines().map(|l| l.to_owned()).collect();
-    let z = Sequence::parse(&mut y, 0).unwrap();
+    let y: Vec<String> = x.lines().map(|l| l.to_owned()).collect();
+    let z = Sequence::parse(&y, 0).unwrap();
     assert_eq!(z.name, "p_draw");
     assert_eq!(z.smd, r"c_scout_animations_anims\c_scout_arms_skeleton.smd");
     assert_eq!(z.activity, "ACT_SECONDARY_VM_DRAW");
@@ -27,6 +29,7 @@ addlayer "test_layer"
     );
     assert_eq!(z.layer[0], "test_layer");
     assert_eq!(z.weightlist, "test_weight");
+    assert_eq!(z.options.fps, Some(22.0));
     assert!(!z.uses_animation);
 }
 
@@ -47,8 +50,8 @@ weightlist "test_weight"
 addlayer "test_layer"
 }"#,
     );
-    let mut y: Vec<String> = x.lines().map(|l| l.to_owned()).collect();
-    let _ = Sequence::parse(&mut y, 0).unwrap();
+    let y: Vec<String> = x.lines().map(|l| l.to_owned()).collect();
+    let _ = Sequence::parse(&y, 0).unwrap();
 }
 
 #[test]
@@ -61,8 +64,8 @@ snap
 { event 5004 1 "Weapon_Pistol.Draw" }
 }"#,
     );
-    let mut y: Vec<String> = x.lines().map(|l| l.to_owned()).collect();
-    let z = Sequence::parse(&mut y, 0).unwrap();
+    let y: Vec<String> = x.lines().map(|l| l.to_owned()).collect();
+    let z = Sequence::parse(&y, 0).unwrap();
     assert_eq!(z.name, "p_draw");
     assert_eq!(z.smd, "p_draw.smd");
     assert_eq!(z.activity, "ACT_SECONDARY_VM_DRAW");
@@ -85,8 +88,8 @@ weightlist "test_weight"
 addlayer "test_layer"
 }"#,
     );
-    let mut y: Vec<String> = x.lines().map(|l| l.to_owned()).collect();
-    let z = Sequence::parse(&mut y, 0).unwrap();
+    let y: Vec<String> = x.lines().map(|l| l.to_owned()).collect();
+    let z = Sequence::parse(&y, 0).unwrap();
     assert_eq!(z.name, "p_draw");
     assert_eq!(z.smd, "p_draw_anim");
     assert_eq!(z.activity, "ACT_SECONDARY_VM_DRAW");
@@ -111,8 +114,8 @@ snap
 { event 5004 1 "Weapon_Pistol.Draw" }
 }"#,
     );
-    let mut y: Vec<String> = x.lines().map(|l| l.to_owned()).collect();
-    let z = Sequence::parse(&mut y, 0).unwrap();
+    let y: Vec<String> = x.lines().map(|l| l.to_owned()).collect();
+    let z = Sequence::parse(&y, 0).unwrap();
     assert_eq!(z.name, "p_draw");
     assert_eq!(z.smd, "p_draw_animation");
     assert_eq!(z.activity, "ACT_SECONDARY_VM_DRAW");
@@ -121,3 +124,110 @@ snap
     assert!(z.layer.is_empty());
     assert!(z.uses_animation);
 }
+
+#[test]
+fn set_fps_test() {
+    let mut qc_data = r#"$sequence "p_draw" "p_draw.smd" {
+fps 30
+}
+$sequence "p_idle" "p_idle.smd" {
+}"#
+    .lines()
+    .map(|line| line.to_owned())
+    .collect::<Vec<String>>();
+    let mut qc = QC::parse("test.qc", qc_data.clone().into_iter()).unwrap();
+    assert_eq!(qc.sequences["p_idle"].options.fps, None);
+
+    qc.set_fps("p_draw", 26.0).unwrap();
+    qc.set_fps("p_idle", 24.5).unwrap();
+    qc_data = qc.document.lines();
+    assert_eq!(qc_data[1], "fps 26");
+    assert_eq!(qc_data[4], "fps 24.5");
+    assert_eq!(qc.sequences["p_draw"].options.fps, Some(26.0));
+    assert_eq!(qc.sequences["p_idle"].options.fps, Some(24.5));
+    assert_eq!(qc.sequences["p_idle"].end, 5);
+}
+
+#[test]
+fn activities_test() {
+    let mut base_data = r#"$sequence "p_draw" "p_draw.smd" {
+activity "ACT_SECONDARY_VM_DRAW" 3
+activity "ACT_VM_DRAW" 1
+fadein 0.2
+}"#
+    .lines()
+    .map(|line| line.to_owned())
+    .collect::<Vec<String>>();
+    let mut base = QC::parse("base.qc", base_data.clone().into_iter()).unwrap();
+    let p_draw = &base.sequences["p_draw"];
+    assert_eq!(p_draw.activity, "ACT_SECONDARY_VM_DRAW");
+    assert_eq!(
+        p_draw.options.activities,
+        vec![
+            Activity {
+                name: "ACT_SECONDARY_VM_DRAW".to_owned(),
+                weight: 3
+            },
+            Activity {
+                name: "ACT_VM_DRAW".to_owned(),
+                weight: 1
+            }
+        ]
+    );
+
+    // the donor only needs one of the base's activities, the base's weights are kept
+    let donor = QC::parse(
+        "donor.qc",
+        r#"$sequence p_draw "donor_draw.smd" {
+activity "ACT_VM_DRAW" 1
+snap
+}"#
+        .lines()
+        .map(|line| line.to_owned()),
+    )
+    .unwrap();
+    assert_eq!(base.merge_status(&donor, "p_draw"), MergeStatus::Replace);
+    base.replace_sequence(&donor, "p_draw").unwrap();
+    base_data = base.document.lines();
+    assert_eq!(
+        base_data,
+        vec![
+            r#"$sequence p_draw "donor_draw.smd" {"#,
+            r#"activity "ACT_SECONDARY_VM_DRAW" 3"#,
+            "snap",
+            r#"activity "ACT_VM_DRAW" 1"#,
+            "}",
+        ]
+    );
+}
+
+#[test]
+fn make_alternate_test() {
+    let base = QC::new("tests/scout/c_scout_animations.qc").unwrap();
+    let mut donor = QC::new("tests/Pistol/c_scout_pistol.qc").unwrap();
+    let name = donor.make_alternate(&base, "p_draw").unwrap();
+    assert_eq!(name, "p_draw_alt");
+    assert!(!donor.sequences.contains_key("p_draw"));
+    let alternate = &donor.sequences["p_draw_alt"];
+    assert_eq!(alternate.name, "p_draw_alt");
+    assert_eq!(alternate.smd, "p_draw.smd");
+    assert_eq!(
+        alternate.block[0],
+        r#"$sequence "p_draw_alt" "p_draw.smd" {"#
+    );
+    assert_eq!(
+        alternate.options.activities,
+        base.sequences["p_draw"].options.activities
+    );
+    assert_eq!(base.merge_status(&donor, "p_draw_alt"), MergeStatus::Append);
+
+    // names that are taken are skipped
+    let mut base = base;
+    base.sequences
+        .insert("p_idle_alt".to_owned(), base.sequences["p_idle"].clone());
+    assert_eq!(
+        donor.make_alternate(&base, "p_idle").unwrap(),
+        "p_idle_alt2"
+    );
+    assert!(donor.make_alternate(&base, "p_nonexistent").is_err());
+}
@@ -0,0 +1,338 @@
+use qcmerger_rust::smd::retarget::BoneMap;
+use qcmerger_rust::smd::{bone_names, dmx, missing_bones, parse_nodes, BoneFrame, Node, Smd};
+use std::fs;
+use std::path::PathBuf;
+
+const DMX: &str = r#"<!-- dmx encoding keyvalues2 1 format model 18 -->
+"DmElement"
+{
+	"id" "elementid" "c3a0e2b2-0000-0000-0000-000000000001"
+	"name" "string" "root"
+	"skeleton" "DmeModel"
+	{
+		"name" "string" "c_scout_arms"
+		// the transform has a name too, it isn't a bone
+		"children" "element_array"
+		[
+			"DmeJoint"
+			{
+				"name" "string" "bip_collar_L"
+				"transform" "DmeTransform"
+				{
+					"name" "string" "bip_collar_L_transform"
+				}
+				"children" "element_array"
+				[
+					"DmeJoint"
+					{
+						"name" "string" "bip_upperArm_L"
+					}
+				]
+			},
+			"DmeJoint"
+			{
+				"name" "string" "weapon \"bone\""
+			}
+		]
+	}
+}
+"#;
+
+#[test]
+fn parse_nodes_test() {
+    let nodes = parse_nodes(fs::read_to_string("tests/Pistol/p_draw.smd").unwrap()).unwrap();
+    assert_eq!(
+        nodes[1],
+        Node {
+            index: 1,
+            name: "bip_collar_L".to_owned(),
+            parent: 0
+        }
+    );
+    assert_eq!(nodes[0].parent, -1);
+
+    assert!(parse_nodes("version 1\nskeleton\nend").is_err());
+    assert!(parse_nodes("version 1\nnodes\n0 root -1\nend").is_err());
+    assert!(parse_nodes("version 1\nnodes\n0 \"root\" -1\n").is_err());
+}
+
+#[test]
+fn dmx_bone_names_test() {
+    assert_eq!(
+        dmx::bone_names(DMX).unwrap(),
+        vec!["bip_collar_L", "bip_upperArm_L", "weapon \"bone\""]
+    );
+    assert!(dmx::bone_names("<!-- dmx encoding binary 2 format model 18 -->").is_err());
+    assert!(dmx::bone_names(&DMX[..DMX.len() - 2]).is_err());
+}
+
+#[test]
+fn missing_bones_test() {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("missing_bones");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("p_draw.DMX"), DMX).unwrap();
+    assert_eq!(bone_names(dir.join("p_draw.DMX")).unwrap().len(), 3);
+
+    let skeleton = vec!["BIP_COLLAR_L".to_owned(), "bip_upperArm_L".to_owned()];
+    let map = BoneMap::default();
+    assert_eq!(
+        missing_bones(dir.join("p_draw.DMX"), &skeleton, &map).unwrap(),
+        vec!["weapon \"bone\""]
+    );
+    assert!(missing_bones("tests/Pistol/p_draw.smd", &skeleton, &map)
+        .unwrap()
+        .contains(&"root".to_owned()));
+
+    // bones are compared after they're renamed
+    let map = BoneMap::parse("root bip_collar_L").unwrap();
+    assert!(!missing_bones("tests/Pistol/p_draw.smd", &skeleton, &map)
+        .unwrap()
+        .contains(&"root".to_owned()));
+}
+
+#[test]
+fn smd_parse_test() {
+    let smd = Smd::new("tests/Pistol/p_draw.smd").unwrap();
+    assert_eq!(
+        smd.header,
+        vec!["// Created by Crowbar 0.33.0.0", "version 1"]
+    );
+    assert_eq!(smd.nodes.len(), 58);
+    assert_eq!(smd.frames[0].time, 0);
+    assert_eq!(smd.frames[0].bones.len(), 58);
+    assert_eq!(
+        smd.frames[0].bones[0].position,
+        [3.919922, 2.632813, -25.03125]
+    );
+    assert_eq!(smd.node("BIP_COLLAR_L").unwrap().index, 1);
+
+    // writing and parsing again gives the same smd
+    assert_eq!(Smd::parse(smd.to_string()).unwrap(), smd);
+
+    assert!(
+        Smd::parse("version 1\nnodes\n0 \"root\" -1\nend\nskeleton\n0 0 0 0 0 0 0\nend").is_err()
+    );
+    assert!(
+        Smd::parse("version 1\nnodes\n0 \"root\" -1\nend\nskeleton\ntime 0\n0 0 0\nend").is_err()
+    );
+}
+
+#[test]
+fn retarget_test() {
+    let mut smd = Smd::parse(
+        r#"version 1
+nodes
+  0 "weapon_bone" -1
+  1 "root" -1
+  2 "bip_hand_R" 1
+end
+skeleton
+  time 0
+    0 1 0 0 0 0 0
+    1 2 0 0 0 0 0
+    2 3 0 0 0 0 0
+end
+triangles
+mat
+0 0 0 0 0 0 1 0 0 2 0 0.5 2 0.5
+1 0 0 0 0 0 1 0 0
+2 0 0 0 0 0 1 0 0
+end
+"#,
+    )
+    .unwrap();
+    let skeleton = vec![
+        "root".to_owned(),
+        "bip_hand_R".to_owned(),
+        "weapon_bone_R".to_owned(),
+    ];
+    let map = BoneMap::parse(
+        r#"// community rig
+"WEAPON_BONE" "weapon_bone_R"
+"#,
+    )
+    .unwrap();
+    smd.retarget(&map, &skeleton).unwrap();
+
+    assert_eq!(
+        smd.nodes,
+        vec![
+            Node {
+                index: 0,
+                name: "root".to_owned(),
+                parent: -1
+            },
+            Node {
+                index: 1,
+                name: "bip_hand_R".to_owned(),
+                parent: 0
+            },
+            Node {
+                index: 2,
+                name: "weapon_bone_R".to_owned(),
+                parent: -1
+            },
+        ]
+    );
+    let positions = smd.frames[0]
+        .bones
+        .iter()
+        .map(|bone| (bone.index, bone.position[0]))
+        .collect::<Vec<(i32, f64)>>();
+    assert_eq!(positions, vec![(0, 2.0), (1, 3.0), (2, 1.0)]);
+    assert_eq!(smd.triangles[1], "2 0 0 0 0 0 1 0 0 2 2 0.5 1 0.5");
+    assert_eq!(smd.triangles[2], "0 0 0 0 0 0 1 0 0");
+
+    // two bones can't end up with the same name
+    let map = BoneMap::parse("weapon_bone_R root").unwrap();
+    assert!(smd.retarget(&map, &skeleton).is_err());
+}
+
+// a bone that moves along x and turns around z, keyed on the first and last frame
+fn moving_bone(last: i32, x: f64, z: f64) -> Smd {
+    Smd::parse(format!(
+        "version 1\nnodes\n0 \"root\" -1\nend\nskeleton\ntime 0\n0 0 0 0 0 0 0\ntime {}\n0 {} 0 0 0 0 {}\nend\n",
+        last, x, z
+    ))
+    .unwrap()
+}
+
+#[test]
+fn resample_test() {
+    // the first and last frames are kept, the ones between are interpolated
+    let mut smd = Smd::parse(
+        "version 1\nnodes\n0 \"root\" -1\nend\nskeleton\ntime 0\n0 0 0 0 0 0 0\ntime 1\n0 2 0 0 0 0 0.5\ntime 2\n0 4 0 0 0 0 1\nend\n",
+    )
+    .unwrap();
+    assert_eq!(smd.frame_count(), 3);
+    smd.resample(5).unwrap();
+    assert_eq!(smd.frame_count(), 5);
+    let bones = smd
+        .frames
+        .iter()
+        .map(|frame| &frame.bones[0])
+        .collect::<Vec<&BoneFrame>>();
+    assert_eq!(bones[0].position, [0.0, 0.0, 0.0]);
+    assert!((bones[2].position[0] - 2.0).abs() < 1e-9);
+    assert!((bones[2].rotation[2] - 0.5).abs() < 1e-9);
+    assert!((bones[4].rotation[2] - 1.0).abs() < 1e-9);
+
+    // a bone that isn't keyed on a frame holds its transform until the next one, like
+    // fill_frames
+    let mut smd = moving_bone(2, 4.0, 0.0);
+    smd.resample(5).unwrap();
+    assert_eq!(
+        root_x(&smd),
+        vec![(0, 0.0), (1, 0.0), (2, 0.0), (3, 2.0), (4, 4.0)]
+    );
+
+    // slerp takes the shortest path, -3 and 3 radians are close
+    let mut smd = Smd::parse(
+        "version 1\nnodes\n0 \"root\" -1\nend\nskeleton\ntime 0\n0 0 0 0 0 0 3\ntime 1\n0 0 0 0 0 0 -3\nend\n",
+    )
+    .unwrap();
+    smd.resample(3).unwrap();
+    assert!(smd.frames[1].bones[0].rotation[2].abs() > 3.1);
+
+    assert!(smd.resample(0).is_err());
+}
+
+#[test]
+fn conform_fps_test() {
+    // 21 frames at 30 fps is 20 frames long, at 24 fps that's 16 frames long
+    let mut smd = Smd::new("tests/Pistol/p_draw.smd").unwrap();
+    assert_eq!(smd.frame_count(), 21);
+    let first = smd.frames[0].clone();
+    smd.conform_fps(30.0, 24.0).unwrap();
+    assert_eq!(smd.frame_count(), 17);
+    assert_eq!(smd.frames[0], first);
+    assert_eq!(smd.frames[16].time, 16);
+
+    let mut smd = moving_bone(1, 1.0, 0.0);
+    smd.conform_fps(26.0, 52.0).unwrap();
+    assert_eq!(smd.frame_count(), 3);
+    assert!(smd.conform_fps(0.0, 30.0).is_err());
+}
+
+// the x position of each frame of the root bone
+fn root_x(smd: &Smd) -> Vec<(i32, f64)> {
+    smd.frames
+        .iter()
+        .map(|frame| (frame.time, frame.bones[0].position[0]))
+        .collect()
+}
+
+#[test]
+fn fill_frames_test() {
+    // bone 1 is only keyed on frame 1, frame 2 is missing
+    let mut smd = Smd::parse(
+        r#"version 1
+nodes
+0 "root" -1
+1 "weapon_bone" 0
+end
+skeleton
+time 3
+0 3 0 0 0 0 0
+time 1
+0 1 0 0 0 0 0
+1 5 0 0 0 0 0
+end
+"#,
+    )
+    .unwrap();
+    smd.fill_frames();
+    assert_eq!(root_x(&smd), vec![(0, 1.0), (1, 1.0), (2, 3.0)]);
+    assert!(smd.frames.iter().all(|frame| frame.bones.len() == 2));
+    assert_eq!(smd.frames[2].bones[1].position[0], 5.0);
+}
+
+#[test]
+fn trim_reverse_test() {
+    let mut smd = moving_bone(4, 4.0, 0.0);
+    smd.trim(1, 3).unwrap();
+    assert_eq!(root_x(&smd), vec![(0, 0.0), (1, 0.0), (2, 0.0)]);
+    assert!(smd.trim(2, 3).is_err());
+    assert!(smd.trim(2, 1).is_err());
+
+    let mut smd = Smd::new("tests/Pistol/p_draw.smd").unwrap();
+    let last = smd.frames[20].bones.clone();
+    smd.reverse();
+    assert_eq!(smd.frame_count(), 21);
+    assert_eq!(smd.frames[0].time, 0);
+    assert_eq!(smd.frames[0].bones, last);
+}
+
+#[test]
+fn concat_test() {
+    let mut smd = moving_bone(1, 1.0, 0.0);
+    // bones are matched by name, ignoring case
+    let other = Smd::parse(
+        r#"version 1
+nodes
+0 "ROOT" -1
+end
+skeleton
+time 0
+0 1 0 0 0 0 0
+time 1
+0 2 0 0 0 0 0
+end
+"#,
+    )
+    .unwrap();
+    smd.concat(&other, true).unwrap();
+    assert_eq!(root_x(&smd), vec![(0, 0.0), (1, 1.0), (2, 2.0)]);
+    smd.concat(&other, false).unwrap();
+    assert_eq!(smd.frame_count(), 5);
+
+    let other = Smd::parse("version 1\nnodes\n0 \"weapon_bone\" -1\nend\nskeleton\nend\n").unwrap();
+    assert!(smd.concat(&other, false).is_err());
+
+    // a frame with a bone that isn't in the nodes
+    let other = Smd::parse(
+        "version 1\nnodes\n0 \"root\" -1\nend\nskeleton\ntime 0\n0 1 0 0 0 0 0\n3 1 0 0 0 0 0\nend\n",
+    )
+    .unwrap();
+    assert!(smd.concat(&other, false).is_err());
+}
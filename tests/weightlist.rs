@@ -1,4 +1,6 @@
-use qcmerger_rust::qc::weightlist::Weightlist;
+use qcmerger_rust::qc::weightlist::{WeightDiff, Weightlist};
+use qcmerger_rust::qc::QC;
+use qcmerger_rust::smd::retarget::BoneMap;
 
 #[test]
 fn parse_test() {
@@ -64,8 +66,8 @@ fn parse_test() {
  "bip_pinky_2_R" 1
 }"#,
     );
-    let mut y: Vec<String> = x.lines().map(|l| l.to_owned()).collect();
-    let z = Weightlist::parse(&mut y, 0).unwrap();
+    let y: Vec<String> = x.lines().map(|l| l.to_owned()).collect();
+    let z = Weightlist::parse(&y, 0).unwrap();
     assert_eq!(z.name, "test_weight");
     assert_eq!(z.start, 0usize);
     assert_eq!(z.end, 59usize);
@@ -140,6 +142,176 @@ fn parse_test_fail() {
  "bip_pinky_2_R" 1
 }"#,
     );
-    let mut y: Vec<String> = x.lines().map(|l| l.to_owned()).collect();
-    let _ = Weightlist::parse(&mut y, 0).unwrap();
+    let y: Vec<String> = x.lines().map(|l| l.to_owned()).collect();
+    let _ = Weightlist::parse(&y, 0).unwrap();
+}
+
+#[test]
+fn rename_weightlist_bones_test() {
+    let mut qc_data = r#"$weightlist "test_weight" {
+"weapon_bone" 1
+vm_weapon_bone 0.5
+"bip_hand_R" 0
+}"#
+    .lines()
+    .map(|line| line.to_owned())
+    .collect::<Vec<String>>();
+    let mut qc = QC::parse("test.qc", qc_data.clone().into_iter()).unwrap();
+    let map = BoneMap::parse("weapon_bone weapon_bone_R\n\"VM_WEAPON_BONE\" \"vm_weapon_bone_R\"")
+        .unwrap();
+    qc.rename_weightlist_bones("test_weight", &map).unwrap();
+    qc_data = qc.document.lines();
+    assert_eq!(qc_data[1], r#""weapon_bone_R" 1"#);
+    assert_eq!(qc_data[2], r#""vm_weapon_bone_R" 0.5"#);
+    assert_eq!(qc_data[3], r#""bip_hand_R" 0"#);
+    assert_eq!(qc.weightlists["test_weight"].block, qc_data);
+}
+
+#[test]
+fn diff_test() {
+    let base = Weightlist::parse(
+        &r#"$weightlist "test_weight" {
+"root" 0
+"weapon_bone" 1
+"bip_hand_R" 0.5
+}"#
+        .lines()
+        .map(|line| line.to_owned())
+        .collect::<Vec<String>>(),
+        0,
+    )
+    .unwrap();
+    assert_eq!(base.weights[2], ("bip_hand_R".to_owned(), 0.5));
+    assert!(base.diff(&base).is_empty());
+
+    let donor = Weightlist::parse(
+        &r#"$weightlist "test_weight" {
+"WEAPON_BONE" 1
+"root" 0
+"bip_hand_R" 1
+"bip_hand_L" 0
+}"#
+        .lines()
+        .map(|line| line.to_owned())
+        .collect::<Vec<String>>(),
+        0,
+    )
+    .unwrap();
+    assert_eq!(
+        base.diff(&donor),
+        vec![
+            WeightDiff {
+                bone: "bip_hand_R".to_owned(),
+                weight: Some(0.5),
+                other_weight: Some(1.0)
+            },
+            WeightDiff {
+                bone: "bip_hand_L".to_owned(),
+                weight: None,
+                other_weight: Some(0.0)
+            }
+        ]
+    );
+}
+
+#[test]
+fn rename_weightlist_test() {
+    let mut qc = QC::parse(
+        "donor.qc",
+        r#"$weightlist "test_weight" {
+"root" 0
+}
+$animation "p_idle_anim" "p_idle.smd" {
+weightlist "test_weight"
+}
+$sequence "p_draw" "p_draw.smd" {
+weightlist "test_weight"
+}
+$sequence "p_idle" "p_idle_anim""#
+            .lines()
+            .map(|line| line.to_owned()),
+    )
+    .unwrap();
+    assert_eq!(
+        qc.used_weightlists(&["p_draw".to_owned(), "p_idle".to_owned()]),
+        vec!["test_weight"]
+    );
+
+    qc.rename_weightlist("test_weight", "test_weight_donor")
+        .unwrap();
+    assert!(!qc.weightlists.contains_key("test_weight"));
+    assert_eq!(
+        qc.weightlists["test_weight_donor"].block[0],
+        r#"$weightlist "test_weight_donor" {"#
+    );
+    assert_eq!(qc.sequences["p_draw"].weightlist, "test_weight_donor");
+    assert_eq!(
+        qc.sequences["p_draw"].write_block()[1],
+        r#"weightlist "test_weight_donor""#
+    );
+    assert_eq!(
+        qc.animations["p_idle_anim"].block[1],
+        r#"weightlist "test_weight_donor""#
+    );
+    assert!(qc.rename_weightlist("test_weight", "other").is_err());
+}
+
+#[test]
+fn replace_weightlist_test() {
+    let mut qc_data = r#"$weightlist "test_weight" {
+"root" 0
+}
+$sequence "p_draw" "p_draw.smd""#
+        .lines()
+        .map(|line| line.to_owned())
+        .collect::<Vec<String>>();
+    let mut qc = QC::parse("base.qc", qc_data.clone().into_iter()).unwrap();
+    let donor = QC::parse(
+        "donor.qc",
+        r#"$weightlist "test_weight" {
+"root" 1
+"weapon_bone" 1
+}"#
+        .lines()
+        .map(|line| line.to_owned()),
+    )
+    .unwrap();
+    qc.replace_weightlist(&donor, "test_weight").unwrap();
+    qc_data = qc.document.lines();
+    assert_eq!(qc_data[2], r#""weapon_bone" 1"#);
+    assert_eq!(qc.weightlists["test_weight"].weights.len(), 2);
+    assert_eq!(qc_data[4], qc.sequences["p_draw"].block[0]);
+}
+
+#[test]
+fn parse_weights_test() {
+    let block = [
+        r#"$weightlist "test_weight" {"#,
+        r#"    "root" 0"#,
+        r#"    "bip_spine" "0.5" // quoted"#,
+        r#"    // "bip_hand_L" 1"#,
+        "    bip_hand_R 1",
+        "}",
+    ]
+    .iter()
+    .map(|line| line.to_string())
+    .collect::<Vec<String>>();
+    let weightlist = Weightlist::parse(&block, 0).unwrap();
+    assert_eq!(
+        weightlist.weights,
+        [
+            ("root".to_owned(), 0.0),
+            ("bip_spine".to_owned(), 0.5),
+            ("bip_hand_R".to_owned(), 1.0)
+        ]
+    );
+
+    // a weight that isn't a number is reported with its line
+    let mut block = block;
+    block[1] = r#"    "root" "half""#.to_owned();
+    let error = Weightlist::parse(&block, 10).unwrap_err().to_string();
+    assert_eq!(
+        error,
+        r#"[$weightlist Error] Weird weight at 12: `    "root" "half"`"#
+    );
 }